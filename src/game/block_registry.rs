@@ -0,0 +1,13 @@
+use core::block::BlockRegistry;
+use std::fs;
+use std::path::Path;
+
+/// properties of every block, shared by the meshes and the physics
+pub fn load_block_registry() -> BlockRegistry {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("res/data/blocks.json");
+    let path = path.to_str().unwrap();
+
+    let data = fs::read_to_string(path).expect("<block_database> Could not read data from file");
+
+    BlockRegistry::new(serde_json::from_str(&data).unwrap())
+}
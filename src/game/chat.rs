@@ -0,0 +1,157 @@
+use crate::input::InputHandler;
+
+use glutin::event::VirtualKeyCode;
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// longest message that can be typed, the server enforces it as well
+pub const MAX_MESSAGE_LENGTH: usize = 256;
+/// messages kept in the history, the open chat scrolls through them
+const MAX_HISTORY: usize = 256;
+/// messages shown at once
+const VISIBLE_LINES: usize = 10;
+/// lines scrolled by a press of page up or page down
+const SCROLL_STEP: usize = 3;
+/// time a message stays on screen while the chat is closed, the last second fading it out
+const DISPLAY_TIME: Duration = Duration::from_secs(10);
+const FADE_TIME: Duration = Duration::from_secs(1);
+
+struct ChatLine {
+    text: String,
+    received: Instant,
+}
+
+/// Messages received from the server and the one being typed
+pub struct Chat {
+    history: VecDeque<ChatLine>,
+    /// some while the input box is open
+    input: Option<String>,
+    /// messages between the newest one and the bottom of the open chat
+    scroll: usize,
+}
+
+impl Chat {
+    pub fn new() -> Self {
+        Self {
+            history: VecDeque::new(),
+            input: None,
+            scroll: 0,
+        }
+    }
+
+    /// the player is typing, the other controls should ignore the keyboard
+    pub fn is_open(&self) -> bool {
+        self.input.is_some()
+    }
+
+    pub fn input(&self) -> Option<&str> {
+        self.input.as_deref()
+    }
+
+    pub fn push(&mut self, text: String) {
+        info!("<chat> {}", text);
+
+        self.history.push_back(ChatLine {
+            text,
+            received: Instant::now(),
+        });
+
+        if self.history.len() > MAX_HISTORY {
+            self.history.pop_front();
+        }
+
+        // the lines being read stay in place
+        if self.scroll > 0 {
+            self.scroll = (self.scroll + 1).min(self.max_scroll());
+        }
+    }
+
+    fn max_scroll(&self) -> usize {
+        self.history.len().saturating_sub(VISIBLE_LINES)
+    }
+
+    /// the open chat shows older messages, the closed one always shows the newest
+    pub fn is_scrolled(&self) -> bool {
+        self.scroll > 0
+    }
+
+    /// handles the typing, returns the message to send once the player presses return
+    pub fn update(&mut self, input: &InputHandler) -> Option<String> {
+        if !self.is_open() {
+            // the key opening the chat is received as text as well, it is not typed
+            if input.just_pressed(VirtualKeyCode::T) || input.just_pressed(VirtualKeyCode::Return) {
+                self.input = Some(String::new());
+            }
+
+            return None;
+        }
+
+        if input.just_pressed(VirtualKeyCode::PageUp) {
+            self.scroll = (self.scroll + SCROLL_STEP).min(self.max_scroll());
+        }
+
+        if input.just_pressed(VirtualKeyCode::PageDown) {
+            self.scroll = self.scroll.saturating_sub(SCROLL_STEP);
+        }
+
+        if input.just_pressed(VirtualKeyCode::Escape) {
+            self.input = None;
+            self.scroll = 0;
+            return None;
+        }
+
+        if input.just_pressed(VirtualKeyCode::Return) {
+            self.scroll = 0;
+            let message = self.input.take().unwrap();
+            let message = message.trim();
+
+            return if message.is_empty() {
+                None
+            } else {
+                Some(message.to_string())
+            };
+        }
+
+        let typed = self.input.as_mut().unwrap();
+
+        if input.just_pressed(VirtualKeyCode::Back) {
+            typed.pop();
+        }
+
+        for c in input.get_text().chars() {
+            if typed.chars().count() < MAX_MESSAGE_LENGTH {
+                typed.push(c);
+            }
+        }
+
+        None
+    }
+
+    /// messages on screen, oldest first, with their opacity. The open chat shows them where it
+    /// is scrolled to, the closed one the latest until they fade out.
+    pub fn visible_lines(&self, now: Instant) -> Vec<(&str, f32)> {
+        let open = self.is_open();
+        let scroll = if open { self.scroll } else { 0 };
+
+        let mut lines = self
+            .history
+            .iter()
+            .rev()
+            .skip(scroll)
+            .take(VISIBLE_LINES)
+            .filter_map(|line| {
+                if open {
+                    return Some((line.text.as_str(), 1.0));
+                }
+
+                let remaining = DISPLAY_TIME.checked_sub(now.duration_since(line.received))?;
+                let opacity = (remaining.as_secs_f32() / FADE_TIME.as_secs_f32()).min(1.0);
+
+                Some((line.text.as_str(), opacity))
+            })
+            .collect::<Vec<(&str, f32)>>();
+
+        lines.reverse();
+        lines
+    }
+}
@@ -0,0 +1,230 @@
+use core::block::Block;
+use core::chunk::{Chunk, ChunkGridCoordinate, ChunkGroup, CHUNK_DEPTH, CHUNK_HEIGHT, CHUNK_WIDTH};
+use core::world::{World, WorldCoordinate, LOAD_DISTANCE};
+use serde::{Deserialize, Serialize};
+
+/// chunks are kept a bit past the load distance so walking along a border does not make them
+/// come and go
+const UNLOAD_DISTANCE: i64 = LOAD_DISTANCE as i64 + 1;
+
+/// block ids the game logic cares about, the others are only known to the block registry
+pub const AIR: u8 = 0;
+pub const WATER: u8 = 9;
+
+/// Blocks of a chunk as they travel from the server, run length encoded
+///
+/// Blocks are visited with z changing fastest, then y, then x, so the block at (x, y, z) is the
+/// one at index `(x * CHUNK_HEIGHT + y) * CHUNK_DEPTH + z`. A chunk is mostly made of long runs
+/// of air and stone, which keeps it around a few kilobytes.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChunkData {
+    pub x: i64,
+    pub z: i64,
+    /// (length, block id) pairs
+    runs: Vec<(u8, u8)>,
+}
+
+impl ChunkData {
+    pub fn from_group(group: &ChunkGroup) -> Self {
+        let coords = group.current.coords;
+        let mut runs: Vec<(u8, u8)> = Vec::new();
+
+        for x in 0..CHUNK_WIDTH {
+            for y in 0..CHUNK_HEIGHT {
+                for z in 0..CHUNK_DEPTH {
+                    let id = group
+                        .get_block(x as i8, y as i16, z as i8)
+                        .map_or(0, |block| block.id as u8);
+
+                    match runs.last_mut() {
+                        Some((length, last)) if *last == id && *length < u8::MAX => *length += 1,
+                        _ => runs.push((1, id)),
+                    }
+                }
+            }
+        }
+
+        Self {
+            x: coords.x,
+            z: coords.z,
+            runs,
+        }
+    }
+
+    pub fn coords(&self) -> ChunkGridCoordinate {
+        ChunkGridCoordinate::new(self.x, self.z)
+    }
+
+    /// none when the runs do not describe exactly one chunk
+    pub fn to_chunk(&self) -> Option<Chunk> {
+        let total: usize = self.runs.iter().map(|(length, _)| *length as usize).sum();
+
+        if total != CHUNK_WIDTH * CHUNK_HEIGHT * CHUNK_DEPTH {
+            return None;
+        }
+
+        let mut chunk = Chunk::new(self.coords());
+        let ids = self
+            .runs
+            .iter()
+            .flat_map(|(length, id)| std::iter::repeat(*id).take(*length as usize));
+
+        for (index, id) in ids.enumerate() {
+            let z = index % CHUNK_DEPTH;
+            let y = (index / CHUNK_DEPTH) % CHUNK_HEIGHT;
+            let x = index / (CHUNK_DEPTH * CHUNK_HEIGHT);
+
+            chunk.set_block(x, y, z, Block { id: id as _ });
+        }
+
+        Some(chunk)
+    }
+}
+
+/// Position of a block in the world
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct BlockCoordinate {
+    pub x: i64,
+    pub y: i64,
+    pub z: i64,
+}
+
+impl BlockCoordinate {
+    /// block containing a world position
+    pub fn from_world(position: WorldCoordinate) -> Self {
+        Self {
+            x: position.x.floor() as i64,
+            y: position.y.floor() as i64,
+            z: position.z.floor() as i64,
+        }
+    }
+
+    /// center of the block
+    pub fn center(&self) -> WorldCoordinate {
+        WorldCoordinate {
+            x: self.x as f32 + 0.5,
+            y: self.y as f32 + 0.5,
+            z: self.z as f32 + 0.5,
+        }
+    }
+
+    pub fn chunk(&self) -> ChunkGridCoordinate {
+        ChunkGridCoordinate::new(
+            self.x.div_euclid(CHUNK_WIDTH as i64),
+            self.z.div_euclid(CHUNK_DEPTH as i64),
+        )
+    }
+
+    /// position inside its chunk, none above or below the world
+    fn local(&self) -> Option<(usize, usize, usize)> {
+        if self.y < 0 || self.y >= CHUNK_HEIGHT as i64 {
+            return None;
+        }
+
+        Some((
+            self.x.rem_euclid(CHUNK_WIDTH as i64) as usize,
+            self.y as usize,
+            self.z.rem_euclid(CHUNK_DEPTH as i64) as usize,
+        ))
+    }
+
+    /// chunks whose mesh changes with this block, its own and the ones it borders
+    pub fn affected_chunks(&self) -> Vec<ChunkGridCoordinate> {
+        let coords = self.chunk();
+        let mut chunks = vec![coords];
+
+        if let Some((x, _, z)) = self.local() {
+            if x == 0 {
+                chunks.push(ChunkGridCoordinate::new(coords.x - 1, coords.z));
+            } else if x == CHUNK_WIDTH - 1 {
+                chunks.push(ChunkGridCoordinate::new(coords.x + 1, coords.z));
+            }
+
+            if z == 0 {
+                chunks.push(ChunkGridCoordinate::new(coords.x, coords.z - 1));
+            } else if z == CHUNK_DEPTH - 1 {
+                chunks.push(ChunkGridCoordinate::new(coords.x, coords.z + 1));
+            }
+        }
+
+        chunks
+    }
+}
+
+/// id of the block at `position`, none when its chunk is not loaded
+pub fn block_at(world: &World, position: BlockCoordinate) -> Option<u8> {
+    let (x, y, z) = position.local()?;
+    let chunk = world.chunks.get(&position.chunk())?;
+
+    Some(chunk.get_block(x, y, z).id as u8)
+}
+
+/// changes a block of a loaded chunk, returns false when the chunk is not loaded
+pub fn set_block(world: &mut World, position: BlockCoordinate, id: u8) -> bool {
+    let (x, y, z) = match position.local() {
+        Some(local) => local,
+        None => return false,
+    };
+
+    match world.chunks.get_mut(&position.chunk()) {
+        Some(chunk) => {
+            chunk.set_block(x, y, z, Block { id: id as _ });
+            true
+        }
+        None => false,
+    }
+}
+
+/// chunk containing a world position
+pub fn chunk_coordinate(position: WorldCoordinate) -> ChunkGridCoordinate {
+    ChunkGridCoordinate::new(
+        (position.x / CHUNK_WIDTH as f32).floor() as i64,
+        (position.z / CHUNK_DEPTH as f32).floor() as i64,
+    )
+}
+
+/// the four chunks sharing a border with the given one
+pub fn neighbours(coords: ChunkGridCoordinate) -> [ChunkGridCoordinate; 4] {
+    [
+        ChunkGridCoordinate::new(coords.x - 1, coords.z),
+        ChunkGridCoordinate::new(coords.x + 1, coords.z),
+        ChunkGridCoordinate::new(coords.x, coords.z - 1),
+        ChunkGridCoordinate::new(coords.x, coords.z + 1),
+    ]
+}
+
+/// number of chunks between two chunks, diagonals count as one
+pub fn chunk_distance(a: ChunkGridCoordinate, b: ChunkGridCoordinate) -> i64 {
+    (a.x - b.x).abs().max((a.z - b.z).abs())
+}
+
+/// chunks within the load distance of `center`
+pub fn chunks_in_range(center: ChunkGridCoordinate) -> Vec<ChunkGridCoordinate> {
+    let distance = LOAD_DISTANCE as i64;
+    let mut chunks = Vec::new();
+
+    for x in -distance..=distance {
+        for z in -distance..=distance {
+            chunks.push(ChunkGridCoordinate::new(center.x + x, center.z + z));
+        }
+    }
+
+    chunks
+}
+
+/// chunks too far from `center` are dropped by the client and sent again by the server when the
+/// player comes back
+pub fn is_out_of_range(coords: ChunkGridCoordinate, center: ChunkGridCoordinate) -> bool {
+    chunk_distance(coords, center) > UNLOAD_DISTANCE
+}
+
+/// puts a chunk received from the server in the world, replacing the previous version
+pub fn insert_chunk(world: &mut World, data: &ChunkData) -> bool {
+    match data.to_chunk() {
+        Some(chunk) => {
+            world.chunks.insert(data.coords(), chunk);
+            true
+        }
+        None => false,
+    }
+}
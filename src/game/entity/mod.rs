@@ -1,3 +1,5 @@
 mod player;
+mod snapshot;
 
-pub use self::player::{Player, PlayerId};
+pub use self::player::{is_valid_username, Player, PlayerId, PlayerState, MAX_USERNAME_LENGTH};
+pub use self::snapshot::SnapshotBuffer;
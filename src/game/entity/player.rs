@@ -1,43 +1,107 @@
-use crate::game::NETWORK_UPDATE_TIMEOUT;
+use crate::game::entity::SnapshotBuffer;
+use crate::game::MovementFlags;
+use crate::network::ServerTime;
 
 use core::world::WorldCoordinate;
-use std::time::Instant;
+use serde::{Deserialize, Serialize};
 
 pub type PlayerId = u128;
 
+pub const MAX_USERNAME_LENGTH: usize = 16;
+
+/// usernames are made of ascii letters, digits and underscores
+pub fn is_valid_username(username: &str) -> bool {
+    !username.is_empty()
+        && username.len() <= MAX_USERNAME_LENGTH
+        && username
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// State of a player at a given time
+#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
+pub struct PlayerState {
+    /// eye position
+    pub position: WorldCoordinate,
+    /// angles in degrees, a positive pitch looks down
+    pub yaw: f32,
+    pub pitch: f32,
+    pub flags: MovementFlags,
+}
+
+impl PlayerState {
+    pub fn new(position: WorldCoordinate) -> Self {
+        Self {
+            position,
+            yaw: 0.0,
+            pitch: 0.0,
+            flags: MovementFlags::default(),
+        }
+    }
+
+    pub fn lerp(&self, other: &PlayerState, percent: f32) -> PlayerState {
+        PlayerState {
+            position: self.position + (other.position - self.position) * percent,
+            yaw: lerp_angle(self.yaw, other.yaw, percent),
+            pitch: self.pitch + (other.pitch - self.pitch) * percent,
+            flags: if percent < 0.5 {
+                self.flags
+            } else {
+                other.flags
+            },
+        }
+    }
+}
+
+/// interpolates along the shortest arc
+fn lerp_angle(from: f32, to: f32, percent: f32) -> f32 {
+    let mut delta = (to - from) % 360.0;
+
+    if delta > 180.0 {
+        delta -= 360.0;
+    } else if delta < -180.0 {
+        delta += 360.0;
+    }
+
+    from + delta * percent
+}
+
 pub struct Player {
-    position: WorldCoordinate,
-    start_position: WorldCoordinate,
-    target_position: WorldCoordinate,
-    last_update: Instant,
+    state: PlayerState,
+    snapshots: SnapshotBuffer,
+    /// unknown until the server sends the player list
+    username: Option<String>,
 }
 
 impl Player {
     pub fn new() -> Self {
         Self {
-            position: WorldCoordinate::zero(),
-            start_position: WorldCoordinate::zero(),
-            target_position: WorldCoordinate::zero(),
-            last_update: Instant::now(),
+            state: PlayerState::new(WorldCoordinate::zero()),
+            snapshots: SnapshotBuffer::new(),
+            username: None,
         }
     }
 
-    pub fn position(&self) -> WorldCoordinate {
-        self.position
+    pub fn state(&self) -> &PlayerState {
+        &self.state
     }
 
-    pub fn set_position(&mut self, position: WorldCoordinate) {
-        self.start_position = self.position;
-        self.target_position = position;
-        self.last_update = Instant::now();
+    pub fn username(&self) -> Option<&str> {
+        self.username.as_deref()
     }
 
-    pub fn update(&mut self) {
-        let percent = (self.last_update.elapsed().as_secs_f32() * 1000.0
-            / (2 * NETWORK_UPDATE_TIMEOUT) as f32)
-            .min(1.0);
+    pub fn set_username(&mut self, username: String) {
+        self.username = Some(username);
+    }
 
-        self.position =
-            self.start_position + (self.target_position - self.start_position) * percent;
+    pub fn push_snapshot(&mut self, time: ServerTime, state: PlayerState) {
+        self.snapshots.insert(time, state);
+    }
+
+    /// `render_time` is the server time at which the player is displayed
+    pub fn update(&mut self, render_time: ServerTime) {
+        if let Some(state) = self.snapshots.sample(render_time) {
+            self.state = state;
+        }
     }
 }
@@ -0,0 +1,127 @@
+use crate::game::entity::PlayerState;
+use crate::network::ServerTime;
+
+use std::collections::VecDeque;
+
+const MAX_SNAPSHOTS: usize = 32;
+/// how long the movement continues past the last snapshot before the player stops
+const MAX_EXTRAPOLATION: ServerTime = 250;
+
+/// States of a remote player ordered by server time
+///
+/// The buffer is sampled a little in the past so there are usually two snapshots surrounding the
+/// sampled time. When packets go missing the last known velocity is used for a short while.
+pub struct SnapshotBuffer {
+    snapshots: VecDeque<(ServerTime, PlayerState)>,
+}
+
+impl SnapshotBuffer {
+    pub fn new() -> Self {
+        Self {
+            snapshots: VecDeque::new(),
+        }
+    }
+
+    /// snapshots can arrive in any order, duplicates are ignored
+    pub fn insert(&mut self, time: ServerTime, state: PlayerState) {
+        let index = self
+            .snapshots
+            .iter()
+            .position(|(t, _)| *t >= time)
+            .unwrap_or_else(|| self.snapshots.len());
+
+        if index < self.snapshots.len() && self.snapshots[index].0 == time {
+            return;
+        }
+
+        self.snapshots.insert(index, (time, state));
+
+        if self.snapshots.len() > MAX_SNAPSHOTS {
+            self.snapshots.pop_front();
+        }
+    }
+
+    pub fn sample(&mut self, time: ServerTime) -> Option<PlayerState> {
+        // only the last snapshot before `time` is still needed
+        while self.snapshots.len() > 2 && self.snapshots[1].0 <= time {
+            self.snapshots.pop_front();
+        }
+
+        if self.snapshots.len() < 2 {
+            return self.snapshots.front().map(|(_, state)| *state);
+        }
+
+        let (from_time, from) = self.snapshots[0];
+        let (to_time, to) = self.snapshots[1];
+
+        if time <= from_time {
+            return Some(from);
+        }
+
+        // past `to_time` means `to` is the latest snapshot, keep going in the same direction
+        let time = time.min(to_time + MAX_EXTRAPOLATION);
+        let percent = (time - from_time) as f32 / (to_time - from_time) as f32;
+
+        Some(from.lerp(&to, percent))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::world::WorldCoordinate;
+
+    fn state(x: f32) -> PlayerState {
+        PlayerState::new(WorldCoordinate { x, y: 0.0, z: 0.0 })
+    }
+
+    fn sample_x(buffer: &mut SnapshotBuffer, time: ServerTime) -> Option<f32> {
+        buffer.sample(time).map(|state| state.position.x)
+    }
+
+    #[test]
+    fn holds_the_only_snapshot() {
+        let mut buffer = SnapshotBuffer::new();
+        assert_eq!(sample_x(&mut buffer, 100), None);
+
+        buffer.insert(100, state(1.0));
+        assert_eq!(sample_x(&mut buffer, 50), Some(1.0));
+        assert_eq!(sample_x(&mut buffer, 500), Some(1.0));
+    }
+
+    #[test]
+    fn interpolates_between_two_snapshots() {
+        let mut buffer = SnapshotBuffer::new();
+        buffer.insert(100, state(0.0));
+        buffer.insert(200, state(10.0));
+
+        assert_eq!(sample_x(&mut buffer, 50), Some(0.0));
+        assert_eq!(sample_x(&mut buffer, 150), Some(5.0));
+        assert_eq!(sample_x(&mut buffer, 200), Some(10.0));
+    }
+
+    #[test]
+    fn orders_snapshots_and_ignores_duplicates() {
+        let mut buffer = SnapshotBuffer::new();
+        buffer.insert(200, state(10.0));
+        buffer.insert(100, state(0.0));
+        buffer.insert(100, state(99.0));
+        buffer.insert(300, state(20.0));
+
+        assert_eq!(buffer.snapshots.len(), 3);
+        assert_eq!(sample_x(&mut buffer, 100), Some(0.0));
+        assert_eq!(sample_x(&mut buffer, 150), Some(5.0));
+        assert_eq!(sample_x(&mut buffer, 250), Some(15.0));
+    }
+
+    #[test]
+    fn extrapolates_for_a_while_past_the_latest_snapshot() {
+        let mut buffer = SnapshotBuffer::new();
+        buffer.insert(100, state(0.0));
+        buffer.insert(200, state(10.0));
+
+        assert_eq!(sample_x(&mut buffer, 250), Some(15.0));
+        assert_eq!(sample_x(&mut buffer, 200 + MAX_EXTRAPOLATION), Some(35.0));
+        assert_eq!(sample_x(&mut buffer, 10_000), Some(35.0));
+    }
+}
@@ -1,70 +1,178 @@
 use crate::game::{
-    entity::{Player, PlayerId},
-    MainPlayer,
+    block_at, chunk_coordinate,
+    entity::{Player, PlayerId, PlayerState},
+    insert_chunk, is_out_of_range, load_block_registry, material_at, neighbours, set_block,
+    BlockCoordinate, Body, Chat, ChunkData, FixedTimestep, MainPlayer, Material, RaycastHit, AIR,
+    WATER,
 };
 use crate::input::InputHandler;
-use crate::network::{NetworkHandler, RemoteInfo};
-use crate::render::renderer::Renderer;
+use crate::network::{
+    ClientMessage, ConnectionState, EncodedSnapshot, NetworkHandler, PlayerListEntry,
+    QuantizedLook, RemoteInfo, Replayer, ServerClock, ServerMessage, ServerTime, Session,
+    SnapshotHistory,
+};
+use crate::render::renderer::{Hud, Renderer};
+use crate::server::IntegratedServer;
 
+use core::block::BlockRegistry;
 use core::events::{ClientEvent, ServerEvent};
 use core::world::{World, WorldCoordinate};
+use glutin::event::{MouseButton, VirtualKeyCode};
 use std::collections::HashMap;
 use std::io;
+use std::path::PathBuf;
 use std::time::Instant;
 
 pub const NETWORK_UPDATE_TIMEOUT: u128 = 50;
+/// simulation ticks per second, a tick lasts a whole number of milliseconds and input time units
+const TICK_RATE: u32 = 50;
+/// where players appear when they join
+pub const SPAWN_POSITION: WorldCoordinate = WorldCoordinate {
+    x: 0.0,
+    y: 70.0,
+    z: 0.0,
+};
+/// block placed until another one is picked
+const DEFAULT_SELECTED_BLOCK: u8 = 1;
+/// remote players are displayed this far in the past so their movement can be interpolated
+pub const DEFAULT_INTERPOLATION_DELAY: ServerTime = 2 * NETWORK_UPDATE_TIMEOUT as ServerTime;
 
 #[derive(Debug, Clone)]
 pub enum GameType {
+    /// singleplayer, played against an integrated server
     Local,
-    // TODO: remove this dead_code attrib
-    #[allow(dead_code)]
     Remote {
         info: RemoteInfo,
     },
+    /// plays back what a server sent during a recorded session
+    Replay {
+        path: PathBuf,
+    },
 }
 
 pub struct Game {
     world: Option<World>,
+    /// tells which blocks the player collides with
+    block_registry: BlockRegistry,
     player: MainPlayer,
     players: HashMap<PlayerId, Player>,
     renderer: Renderer,
     network: Option<NetworkHandler>,
+    server: Option<IntegratedServer>,
+    replay: Option<Replayer>,
     last_network_update: Instant,
+    last_sent_look: Option<QuantizedLook>,
+    /// snapshots received, the next ones are encoded against the latest acknowledged
+    snapshots: SnapshotHistory,
+    last_snapshot_ack: Option<ServerTime>,
+    interpolation_delay: ServerTime,
+    chat: Chat,
+    /// latest player list sent by the server, shown while tab is held
+    player_list: Vec<PlayerListEntry>,
+    show_player_list: bool,
+    show_debug: bool,
+    /// duration of the last frame in seconds, for the debug overlay
+    frame_time: f64,
+    /// the player is simulated in fixed ticks whatever the frame rate
+    timestep: FixedTimestep,
+    /// block the player looks at
+    target: Option<RaycastHit>,
+    /// block placed with a right click, a middle click picks the targeted one
+    selected_block: u8,
 }
 
 impl Game {
-    pub fn new(game_type: GameType) -> io::Result<Self> {
-        let player = MainPlayer::new(WorldCoordinate {
-            x: 0.0,
-            y: 70.0,
-            z: 0.0,
-        });
-
+    /// `username` is what the other players see, a replay shows the recorded one instead
+    pub fn new(game_type: GameType, username: String) -> io::Result<Self> {
         Ok(match game_type.clone() {
-            GameType::Local => Self {
-                world: Some(World::new()),
-                player,
-                players: HashMap::new(),
-                renderer: Renderer::new(0, 0),
-                network: None,
-                last_network_update: Instant::now(),
-            },
+            GameType::Local => {
+                let (server, transport) = IntegratedServer::start()?;
+                let mut network = NetworkHandler::with_transport(Box::new(transport), true);
+                network.send(ClientMessage::Join { username });
+
+                Self {
+                    world: None,
+                    block_registry: load_block_registry(),
+                    player: MainPlayer::new(SPAWN_POSITION, true),
+                    players: HashMap::new(),
+                    renderer: Renderer::new(0, 0),
+                    network: Some(network),
+                    server: Some(server),
+                    replay: None,
+                    last_network_update: Instant::now(),
+                    last_sent_look: None,
+                    snapshots: SnapshotHistory::new(),
+                    last_snapshot_ack: None,
+                    interpolation_delay: DEFAULT_INTERPOLATION_DELAY,
+                    chat: Chat::new(),
+                    player_list: Vec::new(),
+                    show_player_list: false,
+                    show_debug: false,
+                    frame_time: 0.0,
+                    timestep: FixedTimestep::new(TICK_RATE),
+                    target: None,
+                    selected_block: DEFAULT_SELECTED_BLOCK,
+                }
+            }
             GameType::Remote { info } => Self {
                 world: None,
-                player,
+                block_registry: load_block_registry(),
+                player: MainPlayer::new(SPAWN_POSITION, true),
                 players: HashMap::new(),
                 renderer: Renderer::new(0, 0),
                 network: (|| {
-                    let network = NetworkHandler::new(info).ok()?;
-                    network.send(ClientEvent::PlayerConnect);
+                    let mut network = NetworkHandler::new(info).ok()?;
+                    network.send(ClientMessage::Join { username });
                     Some(network)
                 })(),
+                server: None,
+                replay: None,
                 last_network_update: Instant::now(),
+                last_sent_look: None,
+                snapshots: SnapshotHistory::new(),
+                last_snapshot_ack: None,
+                interpolation_delay: DEFAULT_INTERPOLATION_DELAY,
+                chat: Chat::new(),
+                player_list: Vec::new(),
+                show_player_list: false,
+                show_debug: false,
+                frame_time: 0.0,
+                timestep: FixedTimestep::new(TICK_RATE),
+                target: None,
+                selected_block: DEFAULT_SELECTED_BLOCK,
+            },
+            GameType::Replay { path } => Self {
+                world: None,
+                block_registry: load_block_registry(),
+                // the recorded acks move the player where it was
+                player: MainPlayer::new(SPAWN_POSITION, false),
+                players: HashMap::new(),
+                renderer: Renderer::new(0, 0),
+                network: None,
+                server: None,
+                replay: Some(Replayer::open(path)?),
+                last_network_update: Instant::now(),
+                last_sent_look: None,
+                snapshots: SnapshotHistory::new(),
+                last_snapshot_ack: None,
+                interpolation_delay: DEFAULT_INTERPOLATION_DELAY,
+                chat: Chat::new(),
+                player_list: Vec::new(),
+                show_player_list: false,
+                show_debug: false,
+                frame_time: 0.0,
+                timestep: FixedTimestep::new(TICK_RATE),
+                target: None,
+                selected_block: DEFAULT_SELECTED_BLOCK,
             },
         })
     }
 
+    /// `delay` in milliseconds, other players are shown where they were that long ago
+    pub fn set_interpolation_delay(&mut self, delay: ServerTime) {
+        self.interpolation_delay = delay;
+    }
+
     pub fn resize(&mut self, width: usize, height: usize) {
         self.renderer.resize(width, height);
         self.player
@@ -74,75 +182,473 @@ impl Game {
 
     pub fn render(&self) {
         let pals = self.players.values().collect::<Vec<&Player>>();
-        self.renderer.draw(&self.player.camera, &pals)
+        let status = self.connection_status();
+
+        let debug = if self.show_debug {
+            Some(self.debug_lines())
+        } else {
+            None
+        };
+
+        let hud = Hud {
+            status: status.as_deref(),
+            chat: &self.chat,
+            player_list: if self.show_player_list {
+                Some(self.player_list.as_slice())
+            } else {
+                None
+            },
+            debug: debug.as_deref(),
+        };
+
+        let target = self.target.map(|hit| hit.block);
+
+        self.renderer.draw(&self.player.camera, &pals, target, &hud)
+    }
+
+    /// frame time, clock synchronization and connection details
+    fn debug_lines(&self) -> Vec<String> {
+        let mut lines = vec![format!("frame {:.1} ms", self.frame_time * 1000.0)];
+
+        if let Some(network) = self.network.as_ref() {
+            let bandwidth = network.bandwidth();
+
+            lines.push(format!("connection {}", network.state()));
+            lines.push(format!(
+                "up {:.2} KB/s, down {:.2} KB/s",
+                bandwidth.sent_per_second() as f64 / 1024.0,
+                bandwidth.received_per_second() as f64 / 1024.0
+            ));
+        }
+
+        if let Some(hit) = self.target {
+            lines.push(format!(
+                "target {} {} {} {:?} at {:.1}",
+                hit.block.x, hit.block.y, hit.block.z, hit.face, hit.distance
+            ));
+        }
+
+        lines.push(format!("selected block {}", self.selected_block));
+
+        if self.replay.is_some() {
+            lines.push(format!("replay server time {} ms", self.server_time()));
+        }
+
+        if let Some(clock) = self.clock() {
+            lines.push(format!("server time {} ms", clock.now()));
+
+            match clock.offset() {
+                Some(offset) => lines.push(format!("clock offset {:+} ms", offset)),
+                None => lines.push(String::from("clock offset unknown")),
+            }
+
+            match clock.rtt() {
+                Some(rtt) => lines.push(format!("rtt {:.1} ms", rtt.as_secs_f64() * 1000.0)),
+                None => lines.push(String::from("rtt unknown")),
+            }
+        }
+
+        lines
+    }
+
+    /// message describing the connection when it is not usable
+    fn connection_status(&self) -> Option<String> {
+        match self.network.as_ref()?.state() {
+            ConnectionState::Connected => None,
+            state => Some(state.to_string()),
+        }
     }
 
     pub fn update(&mut self, time_delta: f64, input_handler: &InputHandler) {
-        self.poll_network();
-        self.update_world(time_delta, input_handler);
+        self.frame_time = time_delta;
+
+        let ticks = self.timestep.advance(time_delta);
+
+        self.poll_network(ticks);
+        self.update_world(ticks, input_handler);
         self.update_network();
     }
 
-    fn update_world(&mut self, time_delta: f64, input_handler: &InputHandler) {
-        self.player.update(time_delta, &input_handler);
+    fn update_world(&mut self, ticks: u32, input_handler: &InputHandler) {
+        if input_handler.just_pressed(VirtualKeyCode::F7) {
+            if let Some(server) = self.server.as_ref() {
+                server.open_to_lan();
+            }
+        }
+
+        if let Some(text) = self.chat.update(input_handler) {
+            if let Some(network) = self.network.as_mut() {
+                network.send(ClientMessage::Chat { text });
+            }
+        }
+
+        let world = self.world.as_ref();
+        let registry = &self.block_registry;
+        // nothing is walkable before the world arrives
+        let material = |position| {
+            world.map_or(Material::Solid, |world| {
+                material_at(world, registry, position)
+            })
+        };
+
+        // the keyboard belongs to the chat while it is open
+        let input = if self.chat.is_open() {
+            None
+        } else {
+            Some(input_handler)
+        };
+
+        if let Some(input) = input {
+            self.player.look(input);
+        }
+
+        for _ in 0..ticks {
+            self.player
+                .tick(self.timestep.tick_duration(), input, &material);
+        }
+
+        self.player.interpolate(self.timestep.alpha());
+
+        let underwater = self.player.body().is_underwater(&material);
+        self.renderer.set_underwater(underwater);
+
+        self.show_player_list =
+            !self.chat.is_open() && input_handler.is_key_pressed(VirtualKeyCode::Tab);
+
+        if !self.chat.is_open() && input_handler.just_pressed(VirtualKeyCode::F3) {
+            self.show_debug = !self.show_debug;
+        }
+
+        let render_time = self.server_time().saturating_sub(self.interpolation_delay);
 
         for (_, player) in self.players.iter_mut() {
-            player.update();
+            player.update(render_time);
+        }
+
+        self.target = match self.world.as_ref() {
+            Some(world) => self.player.target(world),
+            None => None,
+        };
+
+        if !self.chat.is_open() {
+            self.interact(input_handler);
         }
 
         if let Some(world) = self.world.as_mut() {
-            world.load_around(vec![self.player.position()]);
+            // chunks are streamed by the server, the client only forgets the ones left behind
+            let center = chunk_coordinate(self.player.position());
+            world
+                .chunks
+                .retain(|coords, _| !is_out_of_range(*coords, center));
+
             self.renderer.update(&world, &input_handler);
         }
     }
 
     fn update_network(&mut self) {
-        if let Some(network) = self.network.as_ref() {
+        if let Some(network) = self.network.as_mut() {
             if self.last_network_update.elapsed().as_millis() >= NETWORK_UPDATE_TIMEOUT {
-                network.send(ClientEvent::PlayerMove {
-                    position: self.player.position(),
-                });
+                let inputs = self.player.pending_inputs();
+                let look = QuantizedLook::new(&self.player.state());
+                let snapshot_ack = self.snapshots.latest();
+
+                // nothing to tell when the player did not move nor look around, except for the
+                // snapshots that arrived since
+                let idle = inputs.is_empty() && self.last_sent_look == Some(look);
+
+                if !idle {
+                    network.send(ClientMessage::PlayerUpdate {
+                        inputs,
+                        look,
+                        snapshot_ack,
+                    });
+                    self.last_sent_look = Some(look);
+                    self.last_snapshot_ack = snapshot_ack;
+                } else if snapshot_ack != self.last_snapshot_ack {
+                    if let Some(time) = snapshot_ack {
+                        network.send(ClientMessage::SnapshotAck { time });
+                        self.last_snapshot_ack = snapshot_ack;
+                    }
+                }
 
                 self.last_network_update = Instant::now();
             }
         }
     }
 
-    fn poll_network(&mut self) {
-        if self.network.is_none() {
+    /// a replay moves forward by `ticks` so it plays the same whatever the frame rate
+    fn poll_network(&mut self, ticks: u32) {
+        let messages = if let Some(network) = self.network.as_mut() {
+            match network.process() {
+                Ok(messages) => messages,
+                Err(_) => {
+                    error!("<game> could not process network events");
+                    return;
+                }
+            }
+        } else if let Some(replay) = self.replay.as_mut() {
+            replay.advance(ticks as u64 * 1000 / TICK_RATE as u64)
+        } else {
             return;
-        }
+        };
 
-        if let Ok(events) = self.network.as_ref().unwrap().process() {
-            for event in events {
-                match event {
-                    ServerEvent::PlayerConnected { id } => {
-                        self.players.insert(id, Player::new());
-                    }
-                    ServerEvent::PlayerDisconnected { id } => {
-                        self.players.remove(&id);
+        for message in messages {
+            match message {
+                ServerMessage::Event(event) => self.handle_event(event),
+                ServerMessage::PositionAck {
+                    sequence,
+                    position,
+                    velocity,
+                    on_ground,
+                } => {
+                    let body = Body {
+                        position: position.position(),
+                        velocity,
+                        on_ground,
+                    };
+                    let world = self.world.as_ref();
+                    let registry = &self.block_registry;
+                    let material = |position| {
+                        world.map_or(Material::Solid, |world| {
+                            material_at(world, registry, position)
+                        })
+                    };
+
+                    self.player.reconcile(sequence, body, &material);
+                }
+                ServerMessage::Snapshot { time, players } => self.receive_snapshot(time, players),
+                ServerMessage::Chunk(data) => self.receive_chunk(data),
+                ServerMessage::BlockUpdate { position, block } => {
+                    self.update_block(position, block);
+                }
+                ServerMessage::Chat { from, text } => match from {
+                    Some(id) => {
+                        let line = format!("<{}> {}", self.username(id), text);
+                        self.chat.push(line);
                     }
-                    ServerEvent::PlayerMoved { id, position } => {
-                        if let Some(player) = self.players.get_mut(&id) {
-                            player.set_position(position);
+                    None => self.chat.push(text),
+                },
+                ServerMessage::PlayerList(entries) => {
+                    for entry in entries.iter() {
+                        if let Some(player) = self.players.get_mut(&entry.id) {
+                            player.set_username(entry.username.clone());
                         }
                     }
-                    ServerEvent::ServerInfo { seed, player_ids } => {
-                        self.world = Some(World::from_seed(seed));
 
-                        for id in player_ids.iter() {
-                            self.players.insert(*id, Player::new());
-                        }
-                    }
-                };
+                    self.player_list = entries;
+                }
+                ServerMessage::Abilities { flying } => self.player.set_can_fly(flying),
+            }
+        }
+    }
+
+    fn receive_snapshot(&mut self, time: ServerTime, snapshot: EncodedSnapshot) {
+        let base = match snapshot.base {
+            Some(base) => match self.snapshots.get(base) {
+                Some(states) => Some(states),
+                None => {
+                    warn!(
+                        "<game> snapshot {} is based on unknown snapshot {}",
+                        time, base
+                    );
+                    return;
+                }
+            },
+            None => None,
+        };
+
+        let states = match snapshot.decode(base) {
+            Some(states) => states,
+            None => {
+                warn!("<game> snapshot {} could not be decoded", time);
+                return;
+            }
+        };
+
+        // players left out did not move, they are still given a snapshot to interpolate to
+        for (id, state) in states.iter() {
+            if let Some(player) = self.players.get_mut(id) {
+                player.push_snapshot(time, state.state());
+            }
+        }
+
+        self.snapshots.insert(time, states);
+    }
+
+    fn receive_chunk(&mut self, data: ChunkData) {
+        let world = match self.world.as_mut() {
+            Some(world) => world,
+            None => return,
+        };
+
+        if !insert_chunk(world, &data) {
+            warn!("<game> invalid chunk {}, {}", data.x, data.z);
+            return;
+        }
+
+        // border faces depend on the neighbouring blocks
+        let coords = data.coords();
+        self.renderer.invalidate_chunk(coords);
+
+        for neighbour in neighbours(coords).iter() {
+            self.renderer.invalidate_chunk(*neighbour);
+        }
+    }
+
+    /// changes a block of the world and remeshes the chunks showing it
+    fn update_block(&mut self, position: BlockCoordinate, block: u8) {
+        let world = match self.world.as_mut() {
+            Some(world) => world,
+            None => return,
+        };
+
+        if set_block(world, position, block) {
+            for coords in position.affected_chunks() {
+                self.renderer.remesh_chunk(world, coords);
+            }
+        }
+    }
+
+    /// left click breaks the targeted block, right click places the selected one against it
+    fn interact(&mut self, input_handler: &InputHandler) {
+        let hit = match self.target {
+            Some(hit) => hit,
+            None => return,
+        };
+
+        if input_handler.just_clicked(MouseButton::Left) {
+            self.edit_block(hit.block, AIR);
+        } else if input_handler.just_clicked(MouseButton::Right) {
+            let (x, y, z) = hit.face.normal();
+            let position = BlockCoordinate {
+                x: hit.block.x + x,
+                y: hit.block.y + y,
+                z: hit.block.z + z,
+            };
+
+            if self.can_place(position) {
+                self.edit_block(position, self.selected_block);
+            }
+        } else if input_handler.just_clicked(MouseButton::Middle) {
+            let picked = self
+                .world
+                .as_ref()
+                .and_then(|world| block_at(world, hit.block));
+
+            if let Some(block) = picked {
+                self.selected_block = block;
+            }
+        }
+    }
+
+    /// blocks go in air or water, and not inside the player
+    fn can_place(&self, position: BlockCoordinate) -> bool {
+        let free = self
+            .world
+            .as_ref()
+            .and_then(|world| block_at(world, position))
+            .map_or(false, |block| block == AIR || block == WATER);
+
+        free && !self.player.body().bounding_box().overlaps(position)
+    }
+
+    /// changes a block right away and asks the server to do the same, the server sends the
+    /// block back when it refuses
+    fn edit_block(&mut self, position: BlockCoordinate, block: u8) {
+        let network = match self.network.as_mut() {
+            Some(network) => network,
+            None => return,
+        };
+
+        network.send(ClientMessage::EditBlock { position, block });
+        self.update_block(position, block);
+    }
+
+    fn handle_event(&mut self, event: ServerEvent) {
+        match event {
+            ServerEvent::PlayerConnected { id } => {
+                let mut player = Player::new();
+
+                // the player list can arrive first
+                if let Some(entry) = self.player_list.iter().find(|entry| entry.id == id) {
+                    player.set_username(entry.username.clone());
+                }
+
+                self.players.insert(id, player);
+            }
+            ServerEvent::PlayerDisconnected { id } => {
+                self.players.remove(&id);
             }
+            ServerEvent::PlayerMoved { id, position } => {
+                // plain events carry no timestamp, they are considered as fresh as it gets
+                let time = self.server_time();
+
+                if let Some(player) = self.players.get_mut(&id) {
+                    player.push_snapshot(time, PlayerState::new(position));
+                }
+            }
+            ServerEvent::ServerInfo { seed, player_ids } => {
+                let (session, resumed) = self.session();
+                let own_id = session.map(|session| session.player_id);
+
+                // a resumed session plays in the same world, its chunks are not sent again
+                if self.world.is_none() || !resumed {
+                    self.world = Some(World::from_seed(seed));
+                }
+
+                // a new session starts over with full snapshots
+                if !resumed {
+                    self.snapshots = SnapshotHistory::new();
+                    self.last_snapshot_ack = None;
+                    self.last_sent_look = None;
+                }
+
+                self.players.clear();
+
+                for id in player_ids.iter().filter(|id| Some(**id) != own_id) {
+                    self.players.insert(*id, Player::new());
+                }
+            }
+        };
+    }
+
+    /// name of a player as given by the last player list
+    fn username(&self, id: PlayerId) -> String {
+        self.player_list
+            .iter()
+            .find(|entry| entry.id == id)
+            .map_or_else(|| format!("Player{}", id), |entry| entry.username.clone())
+    }
+
+    /// estimate of the server clock of the live connection
+    fn clock(&self) -> Option<&ServerClock> {
+        self.network.as_ref().map(|network| network.clock())
+    }
+
+    /// shared time used to place the other players, 0 when there is no server. A replay follows
+    /// the recording rather than the wall clock so it plays the same on every run.
+    fn server_time(&self) -> ServerTime {
+        if let Some(replay) = self.replay.as_ref() {
+            replay.server_time().unwrap_or(0)
         } else {
-            error!("<game> could not process network events");
+            self.clock().map_or(0, |clock| clock.now())
         }
     }
 
-    fn send_event(&self, event: ClientEvent) {
+    /// session of the live connection or of the recording being played back, and whether it
+    /// resumed a lost one
+    fn session(&self) -> (Option<Session>, bool) {
         if let Some(network) = self.network.as_ref() {
+            (network.session(), network.resumed())
+        } else if let Some(replay) = self.replay.as_ref() {
+            (replay.session(), replay.resumed())
+        } else {
+            (None, false)
+        }
+    }
+
+    fn send_event(&mut self, event: ClientEvent) {
+        if let Some(network) = self.network.as_mut() {
             network.send(event);
         }
     }
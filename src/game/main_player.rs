@@ -1,36 +1,148 @@
+use crate::game::entity::PlayerState;
+use crate::game::{
+    raycast_world, BlockCoordinate, Body, InputSequence, Material, MovementFlags, MovementInput,
+    RaycastHit,
+};
 use crate::input::InputHandler;
+use crate::network::quantize_angle;
 use crate::render::camera::{Camera, PerspectiveCamera};
 
-use core::world::WorldCoordinate;
+use core::world::{World, WorldCoordinate};
 use glutin::event::VirtualKeyCode;
 use math::vector::Vector3;
+use std::collections::VecDeque;
 
-const SPEED: f64 = 20.0;
 const SENSITIVITY: f32 = 0.2;
+/// inputs kept while waiting for the server, older ones are considered lost
+const MAX_PENDING_INPUTS: usize = 256;
+/// inputs sent in a single message, the most recent ones are sent
+const MAX_INPUTS_PER_MESSAGE: usize = 32;
+/// distance from the eyes to the blocks the player targets, the center of such a block stays
+/// within the reach the server allows
+const REACH: f32 = 7.0;
 
 pub struct MainPlayer {
     pub camera: PerspectiveCamera,
+    body: Body,
+    /// body at the previous tick, the camera is drawn between the two
+    previous_body: Body,
+    flying: bool,
+    /// the server decides whether the player may fly
+    can_fly: bool,
+    sneaking: bool,
+    /// inputs applied locally but not yet acknowledged by the server
+    pending_inputs: VecDeque<MovementInput>,
+    next_sequence: InputSequence,
+    predicted: bool,
 }
 
 impl MainPlayer {
-    pub fn new(position: WorldCoordinate) -> Self {
+    /// a predicted player keeps its inputs until the server acknowledges them
+    pub fn new(position: WorldCoordinate, predicted: bool) -> Self {
         let mut p = Self {
             camera: PerspectiveCamera::new(70.0, 0.1, 1024.0),
+            body: Body::new(position),
+            previous_body: Body::new(position),
+            flying: false,
+            can_fly: false,
+            sneaking: false,
+            pending_inputs: VecDeque::new(),
+            next_sequence: 0,
+            predicted,
         };
 
-        p.set_position(position);
+        p.camera.set_position(position);
         p
     }
 
-    fn set_position(&mut self, position: WorldCoordinate) {
-        self.camera.set_position(position);
+    /// a player that is no longer allowed to fly falls
+    pub fn set_can_fly(&mut self, can_fly: bool) {
+        self.can_fly = can_fly;
+        self.flying &= can_fly;
+    }
+
+    pub fn body(&self) -> &Body {
+        &self.body
     }
 
     pub fn position(&self) -> Vector3 {
         self.camera.position()
     }
 
-    pub fn update(&mut self, time_delta: f64, input: &InputHandler) {
+    pub fn state(&self) -> PlayerState {
+        let angles = self.camera.euler_angles();
+
+        PlayerState {
+            position: self.position(),
+            yaw: angles.y,
+            pitch: angles.x,
+            flags: MovementFlags {
+                flying: self.flying,
+                sneaking: self.sneaking,
+                on_ground: self.body.on_ground,
+            },
+        }
+    }
+
+    /// unit vector pointing where the camera looks
+    pub fn look_direction(&self) -> Vector3 {
+        let angles = self.camera.euler_angles();
+        let yaw = angles.y.to_radians();
+        // a positive pitch looks down
+        let pitch = angles.x.to_radians();
+
+        Vector3 {
+            x: yaw.sin() * pitch.cos(),
+            y: -pitch.sin(),
+            z: yaw.cos() * pitch.cos(),
+        }
+    }
+
+    /// block the player looks at, if it is within reach
+    pub fn target(&self, world: &World) -> Option<RaycastHit> {
+        raycast_world(world, self.position(), self.look_direction(), REACH)
+    }
+
+    /// most recent inputs that were not acknowledged yet, oldest first
+    pub fn pending_inputs(&self) -> Vec<MovementInput> {
+        let skip = self
+            .pending_inputs
+            .len()
+            .saturating_sub(MAX_INPUTS_PER_MESSAGE);
+
+        self.pending_inputs.iter().skip(skip).copied().collect()
+    }
+
+    /// moves the player to the body computed by the server after applying the input `sequence`,
+    /// then replays the inputs the server has not seen yet
+    pub fn reconcile<F: Fn(BlockCoordinate) -> Material>(
+        &mut self,
+        sequence: InputSequence,
+        body: Body,
+        material: &F,
+    ) {
+        while let Some(input) = self.pending_inputs.front() {
+            if input.sequence > sequence {
+                break;
+            }
+
+            self.pending_inputs.pop_front();
+        }
+
+        let predicted = self
+            .pending_inputs
+            .iter()
+            .fold(body, |body, input| input.apply(body, material));
+
+        // the previous tick takes the same correction, the camera would otherwise slide from the
+        // mispredicted position to the corrected one
+        let correction = predicted.position - self.body.position;
+        self.previous_body.position = self.previous_body.position + correction;
+        self.body = predicted;
+    }
+
+    /// turns the camera with the mouse, done every frame so looking around stays smooth
+    pub fn look(&mut self, input: &InputHandler) {
         let cursor_delta = input.get_cursor_delta();
         let camera_delta = Vector3 {
             x: cursor_delta.y as f32,
@@ -49,43 +161,83 @@ impl MainPlayer {
 
         self.camera.set_euler_angles(camera_angles);
 
-        let mut xaxis = 0.0;
-        let mut yaxis = 0.0;
-        let mut zaxis = 0.0;
-
-        if input.is_key_pressed(VirtualKeyCode::W) {
-            zaxis += 1.0;
+        if input.just_pressed(VirtualKeyCode::F) && self.can_fly {
+            self.flying = !self.flying;
         }
+    }
 
-        if input.is_key_pressed(VirtualKeyCode::S) {
-            zaxis -= 1.0;
-        }
+    /// simulates one tick of `time_delta` seconds, the keys are ignored without `input`.
+    /// `material` tells what the player collides with or swims in while walking
+    pub fn tick<F: Fn(BlockCoordinate) -> Material>(
+        &mut self,
+        time_delta: f64,
+        input: Option<&InputHandler>,
+        material: &F,
+    ) {
+        self.previous_body = self.body;
+
+        let mut movement = MovementInput {
+            sequence: self.next_sequence,
+            strafe: 0,
+            vertical: 0,
+            forward: 0,
+            yaw: quantize_angle(self.camera.euler_angles().y),
+            time_delta: MovementInput::quantize_time_delta(time_delta),
+            flying: self.flying,
+        };
 
-        if input.is_key_pressed(VirtualKeyCode::A) {
-            xaxis -= 1.0;
-        }
+        if let Some(input) = input {
+            if input.is_key_pressed(VirtualKeyCode::W) {
+                movement.forward += 1;
+            }
 
-        if input.is_key_pressed(VirtualKeyCode::D) {
-            xaxis += 1.0;
-        }
+            if input.is_key_pressed(VirtualKeyCode::S) {
+                movement.forward -= 1;
+            }
+
+            if input.is_key_pressed(VirtualKeyCode::A) {
+                movement.strafe -= 1;
+            }
+
+            if input.is_key_pressed(VirtualKeyCode::D) {
+                movement.strafe += 1;
+            }
 
-        if input.is_key_pressed(VirtualKeyCode::Space) {
-            yaxis += 1.0;
+            if input.is_key_pressed(VirtualKeyCode::Space) {
+                movement.vertical += 1;
+            }
+
+            if input.is_key_pressed(VirtualKeyCode::LShift) {
+                movement.vertical -= 1;
+            }
         }
 
-        if input.is_key_pressed(VirtualKeyCode::LShift) {
-            yaxis -= 1.0;
+        self.sneaking = !self.flying && movement.vertical < 0;
+
+        // a player standing still or hovering has nothing to simulate
+        let resting = self.flying || self.body.is_resting(material);
+
+        if movement.is_idle() && resting {
+            return;
         }
 
-        let angle = self.camera.euler_angles().y.to_radians();
+        self.body = movement.apply(self.body, material);
 
-        let mut delta = Vector3 {
-            x: xaxis * angle.cos() + zaxis * angle.sin(),
-            y: yaxis,
-            z: -xaxis * angle.sin() + zaxis * angle.cos(),
-        };
-        delta = delta * (SPEED * time_delta) as f32;
+        if self.predicted {
+            self.next_sequence = self.next_sequence.wrapping_add(1);
+            self.pending_inputs.push_back(movement);
 
-        self.set_position(self.camera.position() + delta);
+            if self.pending_inputs.len() > MAX_PENDING_INPUTS {
+                self.pending_inputs.pop_front();
+            }
+        }
+    }
+
+    /// places the camera between the last two ticks, `alpha` is the part of a tick elapsed since
+    /// the last one
+    pub fn interpolate(&mut self, alpha: f32) {
+        let position = self.previous_body.position * (1.0 - alpha) + self.body.position * alpha;
+
+        self.camera.set_position(position);
     }
 }
@@ -1,8 +1,25 @@
+mod block_registry;
+mod chat;
+mod chunk_data;
 pub mod entity;
 mod game;
 mod main_player;
+mod movement;
+mod physics;
+mod raycast;
 mod texture;
+mod timestep;
 
-pub use self::game::{Game, GameType, NETWORK_UPDATE_TIMEOUT};
+pub use self::block_registry::load_block_registry;
+pub use self::chat::{Chat, MAX_MESSAGE_LENGTH};
+pub use self::chunk_data::{
+    block_at, chunk_coordinate, chunk_distance, chunks_in_range, insert_chunk, is_out_of_range,
+    neighbours, set_block, BlockCoordinate, ChunkData, AIR, WATER,
+};
+pub use self::game::{Game, GameType, DEFAULT_INTERPOLATION_DELAY, SPAWN_POSITION};
 pub use self::main_player::MainPlayer;
+pub use self::movement::{InputSequence, MovementFlags, MovementInput};
+pub use self::physics::{material_at, Body, Controls, Material, EYE_HEIGHT};
+pub use self::raycast::{raycast_world, Face, RaycastHit};
 pub use self::texture::TextureDatabase;
+pub use self::timestep::FixedTimestep;
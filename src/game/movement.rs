@@ -0,0 +1,97 @@
+use crate::game::{BlockCoordinate, Body, Controls, Material};
+use crate::network::dequantize_angle;
+
+use math::vector::Vector3;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// flying goes through blocks at a constant speed
+const FLYING_SPEED: f32 = 20.0;
+/// steps per second of the duration of an input, a tenth of a millisecond
+const TIME_SCALE: f64 = 10000.0;
+
+pub type InputSequence = u32;
+
+/// What the player is doing, other players use it to animate them
+#[derive(Debug, Copy, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct MovementFlags {
+    pub flying: bool,
+    pub sneaking: bool,
+    pub on_ground: bool,
+}
+
+/// Movement command produced by one update of the main player
+///
+/// The client applies it right away to predict its position, the server applies the same
+/// commands in the same order to compute the authoritative one. Angles and durations are
+/// quantized when the input is made so both apply the exact same values.
+#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
+pub struct MovementInput {
+    pub sequence: InputSequence,
+    /// each axis is either -1, 0 or 1
+    pub strafe: i8,
+    pub vertical: i8,
+    pub forward: i8,
+    /// camera yaw, see `quantize_angle`
+    pub yaw: u16,
+    /// duration in tenths of a millisecond, see `quantize_time_delta`
+    pub time_delta: u16,
+    /// flying ignores gravity and blocks, walking jumps and sneaks with the vertical axis
+    pub flying: bool,
+}
+
+impl MovementInput {
+    /// `time_delta` in seconds, frames longer than 6.5s are cut short
+    pub fn quantize_time_delta(time_delta: f64) -> u16 {
+        (time_delta * TIME_SCALE)
+            .round()
+            .max(0.0)
+            .min(u16::MAX as f64) as u16
+    }
+
+    pub fn duration(&self) -> Duration {
+        Duration::from_secs_f64(self.time_delta as f64 / TIME_SCALE)
+    }
+
+    pub fn is_idle(&self) -> bool {
+        self.strafe == 0 && self.vertical == 0 && self.forward == 0
+    }
+
+    /// moves the body, `material` tells what a walking player collides with or swims in
+    pub fn apply<F: Fn(BlockCoordinate) -> Material>(&self, body: Body, material: &F) -> Body {
+        let xaxis = self.strafe as f32;
+        let zaxis = self.forward as f32;
+        let angle = dequantize_angle(self.yaw).to_radians();
+        let time_delta = (self.time_delta as f64 / TIME_SCALE) as f32;
+
+        let mut direction = Vector3 {
+            x: xaxis * angle.cos() + zaxis * angle.sin(),
+            y: 0.0,
+            z: -xaxis * angle.sin() + zaxis * angle.cos(),
+        };
+
+        if self.flying {
+            direction.y = self.vertical as f32;
+
+            return Body {
+                position: body.position + direction * (FLYING_SPEED * time_delta),
+                ..Body::new(body.position)
+            };
+        }
+
+        // walking diagonally is not faster
+        let length = (direction.x * direction.x + direction.z * direction.z).sqrt();
+
+        if length > 1.0 {
+            direction = direction * (1.0 / length);
+        }
+
+        let controls = Controls {
+            direction,
+            jump: self.vertical > 0,
+            sneak: self.vertical < 0,
+        };
+
+        body.walk(&controls, time_delta, material)
+    }
+}
@@ -0,0 +1,532 @@
+use crate::game::{block_at, BlockCoordinate, WATER};
+
+use core::block::BlockRegistry;
+use core::chunk::CHUNK_HEIGHT;
+use core::world::{World, WorldCoordinate};
+use math::vector::Vector3;
+use serde::{Deserialize, Serialize};
+
+/// size of the box a player collides with
+pub const PLAYER_WIDTH: f32 = 0.6;
+pub const PLAYER_HEIGHT: f32 = 1.8;
+/// distance between the feet and the eyes of a player
+pub const EYE_HEIGHT: f32 = 1.5;
+/// in blocks per second squared
+const GRAVITY: f32 = 32.0;
+const TERMINAL_SPEED: f32 = 60.0;
+/// vertical speed given by a jump, enough to get on a block
+const JUMP_SPEED: f32 = 9.0;
+const WALK_SPEED: f32 = 4.5;
+const SNEAK_SPEED: f32 = 1.5;
+const SWIM_SPEED: f32 = 2.5;
+/// part of the gravity left once the buoyancy of a fluid is taken away
+const FLUID_GRAVITY: f32 = 0.15;
+/// vertical acceleration when swimming up or diving
+const SWIM_ACCELERATION: f32 = 20.0;
+/// part of the vertical speed a fluid takes away every second
+const FLUID_DRAG: f32 = 5.0;
+/// depth of fluid above the feet needed to swim
+const SWIM_DEPTH: f32 = 0.4;
+/// ledges up to this high are climbed without jumping, a whole block takes a jump
+const STEP_HEIGHT: f32 = 0.6;
+/// longest simulated step, longer inputs are split so the outcome barely depends on how long
+/// the frames are
+const MAX_STEP: f32 = 0.01;
+/// a box this close to a block touches it, which absorbs the rounding of the positions sent over
+/// the network
+const CONTACT_EPSILON: f32 = 1.0 / 256.0;
+
+const HORIZONTAL_AXES: [usize; 2] = [0, 2];
+/// the vertical movement is resolved first so walking off a ledge does not catch on it
+const AXIS_ORDER: [usize; 3] = [1, 0, 2];
+
+/// How a block affects the bodies in it
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Material {
+    Empty,
+    Fluid,
+    /// collides with the bodies
+    Solid,
+}
+
+/// opaque blocks are solid and water is a fluid. Chunks that are not loaded hold the players in
+/// place until they arrive, and the sky is open above the top of the world.
+pub fn material_at(world: &World, registry: &BlockRegistry, position: BlockCoordinate) -> Material {
+    if position.y >= CHUNK_HEIGHT as i64 {
+        return Material::Empty;
+    }
+
+    match block_at(world, position) {
+        None => Material::Solid,
+        Some(WATER) => Material::Fluid,
+        Some(id) if registry.is_opaque(id as _) => Material::Solid,
+        Some(_) => Material::Empty,
+    }
+}
+
+/// Axis aligned box, axes are indexed 0 for x, 1 for y and 2 for z
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct BoundingBox {
+    pub min: [f32; 3],
+    pub max: [f32; 3],
+}
+
+impl BoundingBox {
+    /// box of a player whose eyes are at `position`
+    pub fn player(position: WorldCoordinate) -> Self {
+        let half_width = PLAYER_WIDTH / 2.0;
+        let feet = position.y - EYE_HEIGHT;
+
+        Self {
+            min: [position.x - half_width, feet, position.z - half_width],
+            max: [
+                position.x + half_width,
+                feet + PLAYER_HEIGHT,
+                position.z + half_width,
+            ],
+        }
+    }
+
+    /// indicates the box and the block share some volume, touching is not enough
+    pub fn overlaps(&self, block: BlockCoordinate) -> bool {
+        let block_min = [block.x as f32, block.y as f32, block.z as f32];
+
+        (0..3).all(|axis| {
+            block_min[axis] < self.max[axis] - CONTACT_EPSILON
+                && block_min[axis] + 1.0 > self.min[axis] + CONTACT_EPSILON
+        })
+    }
+
+    fn offset(&self, axis: usize, distance: f32) -> Self {
+        let mut moved = *self;
+        moved.min[axis] += distance;
+        moved.max[axis] += distance;
+        moved
+    }
+
+    /// blocks the box is in or touches
+    fn blocks(&self) -> Vec<BlockCoordinate> {
+        let mut blocks = Vec::new();
+        let min = [
+            self.min[0].floor() as i64,
+            self.min[1].floor() as i64,
+            self.min[2].floor() as i64,
+        ];
+        let max = [
+            self.max[0].floor() as i64,
+            self.max[1].floor() as i64,
+            self.max[2].floor() as i64,
+        ];
+
+        for x in min[0]..=max[0] {
+            for y in min[1]..=max[1] {
+                for z in min[2]..=max[2] {
+                    blocks.push(BlockCoordinate { x, y, z });
+                }
+            }
+        }
+
+        blocks
+    }
+
+    /// how far the box goes along `axis` when asked to move by `distance`, it stops against the
+    /// first solid block on the way. Blocks it is already stuck in are ignored so it can get out.
+    fn clip<F: Fn(BlockCoordinate) -> Material>(
+        &self,
+        axis: usize,
+        distance: f32,
+        material: &F,
+    ) -> f32 {
+        if distance == 0.0 {
+            return 0.0;
+        }
+
+        let mut swept = *self;
+
+        if distance > 0.0 {
+            swept.max[axis] += distance;
+        } else {
+            swept.min[axis] += distance;
+        }
+
+        let mut allowed = distance;
+
+        for block in swept.blocks() {
+            let block_min = [block.x as f32, block.y as f32, block.z as f32];
+
+            // blocks along the sides do not stop the movement
+            let beside = (0..3).filter(|other| *other != axis).any(|other| {
+                block_min[other] >= self.max[other] - CONTACT_EPSILON
+                    || block_min[other] + 1.0 <= self.min[other] + CONTACT_EPSILON
+            });
+
+            if beside || material(block) != Material::Solid {
+                continue;
+            }
+
+            if distance > 0.0 && block_min[axis] >= self.max[axis] - CONTACT_EPSILON {
+                allowed = allowed.min((block_min[axis] - self.max[axis]).max(0.0));
+            } else if distance < 0.0 && block_min[axis] + 1.0 <= self.min[axis] + CONTACT_EPSILON {
+                allowed = allowed.max((block_min[axis] + 1.0 - self.min[axis]).min(0.0));
+            }
+        }
+
+        allowed
+    }
+
+    /// moves along each axis in turn, returns the distance covered on each of them
+    fn slide<F: Fn(BlockCoordinate) -> Material>(
+        &self,
+        wanted: [f32; 3],
+        material: &F,
+    ) -> [f32; 3] {
+        let mut moved = [0.0; 3];
+        let mut current = *self;
+
+        for axis in AXIS_ORDER.iter() {
+            moved[*axis] = current.clip(*axis, wanted[*axis], material);
+            current = current.offset(*axis, moved[*axis]);
+        }
+
+        moved
+    }
+
+    /// climbs up to `STEP_HEIGHT`, moves horizontally then goes back down as far as it can
+    fn step_up<F: Fn(BlockCoordinate) -> Material>(
+        &self,
+        wanted: [f32; 3],
+        material: &F,
+    ) -> [f32; 3] {
+        let up = self.clip(1, STEP_HEIGHT, material);
+        let raised = self.offset(1, up);
+        let horizontal = raised.slide([wanted[0], 0.0, wanted[2]], material);
+        let moved = raised.offset(0, horizontal[0]).offset(2, horizontal[2]);
+        let down = moved.clip(1, wanted[1] - up, material);
+
+        [horizontal[0], up + down, horizontal[2]]
+    }
+}
+
+/// What a walking player asks its body to do
+#[derive(Debug, Copy, Clone)]
+pub struct Controls {
+    /// horizontal, at most 1 long
+    pub direction: Vector3,
+    pub jump: bool,
+    pub sneak: bool,
+}
+
+/// Position and motion of a walking player
+///
+/// The client and the server run the same inputs through it, the outcome only depends on the
+/// inputs and on the blocks around.
+#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
+pub struct Body {
+    /// eye position
+    pub position: WorldCoordinate,
+    /// in blocks per second
+    pub velocity: Vector3,
+    pub on_ground: bool,
+}
+
+impl Body {
+    pub fn new(position: WorldCoordinate) -> Self {
+        Self {
+            position,
+            velocity: Vector3 {
+                x: 0.0,
+                y: 0.0,
+                z: 0.0,
+            },
+            on_ground: false,
+        }
+    }
+
+    pub fn bounding_box(&self) -> BoundingBox {
+        BoundingBox::player(self.position)
+    }
+
+    /// standing on something, nothing happens until the player moves
+    pub fn is_resting<F: Fn(BlockCoordinate) -> Material>(&self, material: &F) -> bool {
+        self.on_ground
+            && !self.is_swimming(material)
+            && self.bounding_box().clip(1, -CONTACT_EPSILON, material) == 0.0
+    }
+
+    /// deep enough in a fluid to swim
+    pub fn is_swimming<F: Fn(BlockCoordinate) -> Material>(&self, material: &F) -> bool {
+        let feet = self.position.y - EYE_HEIGHT;
+        let waist = BlockCoordinate::from_world(WorldCoordinate {
+            y: feet + SWIM_DEPTH,
+            ..self.position
+        });
+
+        material(waist) == Material::Fluid
+    }
+
+    /// the eyes are in a fluid
+    pub fn is_underwater<F: Fn(BlockCoordinate) -> Material>(&self, material: &F) -> bool {
+        material(BlockCoordinate::from_world(self.position)) == Material::Fluid
+    }
+
+    /// simulates `time_delta` seconds in steps of at most `MAX_STEP`
+    pub fn walk<F: Fn(BlockCoordinate) -> Material>(
+        &self,
+        controls: &Controls,
+        time_delta: f32,
+        material: &F,
+    ) -> Body {
+        if time_delta <= 0.0 {
+            return *self;
+        }
+
+        let steps = (time_delta / MAX_STEP).ceil() as u32;
+        let step = time_delta / steps as f32;
+
+        (0..steps).fold(*self, |body, _| body.step(controls, step, material))
+    }
+
+    fn step<F: Fn(BlockCoordinate) -> Material>(
+        &self,
+        controls: &Controls,
+        time_delta: f32,
+        material: &F,
+    ) -> Body {
+        let swimming = self.is_swimming(material);
+
+        let speed = if controls.sneak {
+            SNEAK_SPEED
+        } else if swimming {
+            SWIM_SPEED
+        } else {
+            WALK_SPEED
+        };
+
+        let mut velocity = Vector3 {
+            x: controls.direction.x * speed,
+            y: self.velocity.y,
+            z: controls.direction.z * speed,
+        };
+
+        if swimming {
+            // the buoyancy cancels most of the gravity and the drag slows down falls and swims
+            let acceleration = if controls.jump {
+                SWIM_ACCELERATION
+            } else if controls.sneak {
+                -SWIM_ACCELERATION
+            } else {
+                -GRAVITY * FLUID_GRAVITY
+            };
+
+            velocity.y =
+                (velocity.y + acceleration * time_delta) * (1.0 - FLUID_DRAG * time_delta).max(0.0);
+        } else {
+            if controls.jump && self.on_ground {
+                velocity.y = JUMP_SPEED;
+            }
+
+            velocity.y = (velocity.y - GRAVITY * time_delta).max(-TERMINAL_SPEED);
+        }
+
+        let wanted = [
+            velocity.x * time_delta,
+            velocity.y * time_delta,
+            velocity.z * time_delta,
+        ];
+
+        let bounding_box = self.bounding_box();
+        let mut moved = bounding_box.slide(wanted, material);
+
+        let blocked = HORIZONTAL_AXES
+            .iter()
+            .any(|axis| moved[*axis] != wanted[*axis]);
+
+        // swimmers climb on the shore the same way
+        if (self.on_ground || swimming) && blocked {
+            let stepped = bounding_box.step_up(wanted, material);
+
+            if squared_horizontal_distance(stepped) > squared_horizontal_distance(moved) {
+                moved = stepped;
+            }
+        }
+
+        let on_ground = wanted[1] < 0.0 && moved[1] > wanted[1];
+
+        if moved[0] != wanted[0] {
+            velocity.x = 0.0;
+        }
+
+        if moved[1] != wanted[1] {
+            velocity.y = 0.0;
+        }
+
+        if moved[2] != wanted[2] {
+            velocity.z = 0.0;
+        }
+
+        Body {
+            position: self.position
+                + Vector3 {
+                    x: moved[0],
+                    y: moved[1],
+                    z: moved[2],
+                },
+            velocity,
+            on_ground,
+        }
+    }
+}
+
+fn squared_horizontal_distance(moved: [f32; 3]) -> f32 {
+    moved[0] * moved[0] + moved[2] * moved[2]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// solid ground below y = 0
+    fn floor(block: BlockCoordinate) -> Material {
+        if block.y < 0 {
+            Material::Solid
+        } else {
+            Material::Empty
+        }
+    }
+
+    /// a body whose feet are at `y`
+    fn body(x: f32, y: f32, z: f32, on_ground: bool) -> Body {
+        Body {
+            on_ground,
+            ..Body::new(WorldCoordinate {
+                x,
+                y: y + EYE_HEIGHT,
+                z,
+            })
+        }
+    }
+
+    fn feet(body: &Body) -> f32 {
+        body.position.y - EYE_HEIGHT
+    }
+
+    fn controls(x: f32, z: f32, jump: bool) -> Controls {
+        Controls {
+            direction: Vector3 { x, y: 0.0, z },
+            jump,
+            sneak: false,
+        }
+    }
+
+    fn assert_close(actual: f32, expected: f32) {
+        assert!(
+            (actual - expected).abs() < 1e-3,
+            "{} is not {}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn lands_on_a_floor() {
+        let body = body(0.5, 2.0, 0.5, false).walk(&controls(0.0, 0.0, false), 1.0, &floor);
+
+        assert_close(feet(&body), 0.0);
+        assert_eq!(body.velocity.y, 0.0);
+        assert!(body.on_ground);
+    }
+
+    #[test]
+    fn stops_against_a_wall() {
+        let wall_x = |block: BlockCoordinate| match block.x {
+            x if x >= 2 => Material::Solid,
+            _ => floor(block),
+        };
+        let wall_z = |block: BlockCoordinate| match block.z {
+            z if z >= 2 => Material::Solid,
+            _ => floor(block),
+        };
+        let start = body(0.5, 0.0, 0.5, true);
+
+        let moved = start.walk(&controls(1.0, 0.0, false), 1.0, &wall_x);
+        assert_close(moved.position.x, 2.0 - PLAYER_WIDTH / 2.0);
+        assert_close(moved.position.z, 0.5);
+        assert_eq!(moved.velocity.x, 0.0);
+
+        let moved = start.walk(&controls(0.0, 1.0, false), 1.0, &wall_z);
+        assert_close(moved.position.z, 2.0 - PLAYER_WIDTH / 2.0);
+        assert_close(moved.position.x, 0.5);
+        assert_eq!(moved.velocity.z, 0.0);
+    }
+
+    #[test]
+    fn steps_up_a_low_ledge() {
+        // a block whose top is half a block above the feet, like the shore for a swimmer
+        let ledge = |block: BlockCoordinate| match (block.x, block.y) {
+            (x, 0) if x >= 1 => Material::Solid,
+            _ => floor(block),
+        };
+        let start = body(1.0 - PLAYER_WIDTH / 2.0, 0.5, 0.5, true);
+
+        let moved = start.walk(&controls(1.0, 0.0, false), 0.5, &ledge);
+        assert_close(feet(&moved), 1.0);
+        assert!(moved.position.x > 1.0 + PLAYER_WIDTH / 2.0);
+    }
+
+    #[test]
+    fn jumps_on_a_block() {
+        let ledge = |block: BlockCoordinate| match (block.x, block.y) {
+            (x, 0) if x >= 1 => Material::Solid,
+            _ => floor(block),
+        };
+        let start = body(0.5, 0.0, 0.5, true);
+
+        let walked = start.walk(&controls(1.0, 0.0, false), 1.0, &ledge);
+        assert_close(walked.position.x, 1.0 - PLAYER_WIDTH / 2.0);
+        assert_close(feet(&walked), 0.0);
+
+        // jumps once, it would keep jumping on the block otherwise
+        let jumped = start
+            .walk(&controls(1.0, 0.0, true), MAX_STEP, &ledge)
+            .walk(&controls(1.0, 0.0, false), 1.0, &ledge);
+        assert_close(feet(&jumped), 1.0);
+        assert!(jumped.position.x > 1.0 + PLAYER_WIDTH / 2.0);
+    }
+
+    #[test]
+    fn jumps_as_high_as_its_speed_allows() {
+        let mut body = body(0.5, 0.0, 0.5, true).walk(&controls(0.0, 0.0, true), MAX_STEP, &floor);
+        let mut height = feet(&body);
+
+        while body.velocity.y > 0.0 {
+            body = body.walk(&controls(0.0, 0.0, false), MAX_STEP, &floor);
+            height = height.max(feet(&body));
+        }
+
+        // the steps lose a little of the height of a continuous jump
+        let expected = JUMP_SPEED * JUMP_SPEED / (2.0 * GRAVITY);
+        assert!(height > 1.0);
+        assert!((height - expected).abs() < 0.1, "jumped {}", height);
+    }
+
+    #[test]
+    fn moves_the_same_whatever_the_length_of_the_inputs() {
+        let wall = |block: BlockCoordinate| match block.x {
+            x if x >= 1 => Material::Solid,
+            _ => floor(block),
+        };
+        let start = body(0.5, 0.0, 0.5, true);
+        let controls = controls(0.8, 0.6, true);
+
+        let long = start.walk(&controls, 0.1, &wall);
+        let short = (0..10).fold(start, |body, _| body.walk(&controls, 0.01, &wall));
+
+        assert_eq!(long.position.x, short.position.x);
+        assert_eq!(long.position.y, short.position.y);
+        assert_eq!(long.position.z, short.position.z);
+        assert_eq!(long.velocity.x, short.velocity.x);
+        assert_eq!(long.velocity.y, short.velocity.y);
+        assert_eq!(long.velocity.z, short.velocity.z);
+        assert_eq!(long.on_ground, short.on_ground);
+    }
+}
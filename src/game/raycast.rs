@@ -0,0 +1,287 @@
+use crate::game::{block_at, BlockCoordinate, AIR, WATER};
+
+use core::world::{World, WorldCoordinate};
+use math::vector::Vector3;
+
+/// Face of a block, named like the faces of the chunk meshes
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Face {
+    /// facing -z
+    Front,
+    /// facing +z
+    Back,
+    /// facing -x
+    Left,
+    /// facing +x
+    Right,
+    Top,
+    Bottom,
+}
+
+impl Face {
+    /// face a ray enters a block through when it steps along `axis` (0 for x, 1 for y, 2 for z)
+    /// in the direction `step`
+    fn entered(axis: usize, step: i64) -> Self {
+        match (axis, step > 0) {
+            (0, true) => Face::Left,
+            (0, false) => Face::Right,
+            (1, true) => Face::Bottom,
+            (1, false) => Face::Top,
+            (_, true) => Face::Front,
+            (_, false) => Face::Back,
+        }
+    }
+
+    /// offset to the block on the other side of the face
+    pub fn normal(&self) -> (i64, i64, i64) {
+        match self {
+            Face::Front => (0, 0, -1),
+            Face::Back => (0, 0, 1),
+            Face::Left => (-1, 0, 0),
+            Face::Right => (1, 0, 0),
+            Face::Top => (0, 1, 0),
+            Face::Bottom => (0, -1, 0),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct RaycastHit {
+    pub block: BlockCoordinate,
+    /// face the ray entered the block through
+    pub face: Face,
+    /// distance from the origin of the ray to the face
+    pub distance: f32,
+}
+
+/// Walks the blocks crossed by a ray, in order, and returns the first one `is_target` accepts
+///
+/// The block the ray starts in is skipped, so a player standing in a block still targets the
+/// ones around it. Nothing is hit past `max_distance`.
+pub fn raycast<F: Fn(BlockCoordinate) -> bool>(
+    origin: WorldCoordinate,
+    direction: Vector3,
+    max_distance: f32,
+    is_target: F,
+) -> Option<RaycastHit> {
+    let length =
+        (direction.x * direction.x + direction.y * direction.y + direction.z * direction.z).sqrt();
+
+    if length == 0.0 || !length.is_finite() || !max_distance.is_finite() {
+        return None;
+    }
+
+    let origin = [origin.x, origin.y, origin.z];
+    let direction = [
+        direction.x / length,
+        direction.y / length,
+        direction.z / length,
+    ];
+
+    let mut block = [
+        origin[0].floor() as i64,
+        origin[1].floor() as i64,
+        origin[2].floor() as i64,
+    ];
+    let mut step = [0i64; 3];
+    // distance along the ray to the next block boundary of each axis
+    let mut next_boundary = [f32::INFINITY; 3];
+    // distance along the ray between two boundaries of each axis
+    let mut boundary_spacing = [f32::INFINITY; 3];
+
+    for axis in 0..3 {
+        if direction[axis] > 0.0 {
+            step[axis] = 1;
+            next_boundary[axis] = (block[axis] as f32 + 1.0 - origin[axis]) / direction[axis];
+            boundary_spacing[axis] = 1.0 / direction[axis];
+        } else if direction[axis] < 0.0 {
+            step[axis] = -1;
+            next_boundary[axis] = (origin[axis] - block[axis] as f32) / -direction[axis];
+            boundary_spacing[axis] = 1.0 / -direction[axis];
+        }
+    }
+
+    loop {
+        let axis = if next_boundary[0] < next_boundary[1] {
+            if next_boundary[0] < next_boundary[2] {
+                0
+            } else {
+                2
+            }
+        } else if next_boundary[1] < next_boundary[2] {
+            1
+        } else {
+            2
+        };
+
+        let distance = next_boundary[axis];
+
+        if distance > max_distance {
+            return None;
+        }
+
+        block[axis] += step[axis];
+        next_boundary[axis] += boundary_spacing[axis];
+
+        let coords = BlockCoordinate {
+            x: block[0],
+            y: block[1],
+            z: block[2],
+        };
+
+        if is_target(coords) {
+            return Some(RaycastHit {
+                block: coords,
+                face: Face::entered(axis, step[axis]),
+                distance,
+            });
+        }
+    }
+}
+
+/// block the player can interact with, anything but air and water
+pub fn is_targetable(id: u8) -> bool {
+    id != AIR && id != WATER
+}
+
+/// first block of the loaded chunks the ray hits, unloaded chunks are seen through
+pub fn raycast_world(
+    world: &World,
+    origin: WorldCoordinate,
+    direction: Vector3,
+    max_distance: f32,
+) -> Option<RaycastHit> {
+    raycast(origin, direction, max_distance, |position| {
+        block_at(world, position).map_or(false, is_targetable)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn point(x: f32, y: f32, z: f32) -> WorldCoordinate {
+        WorldCoordinate { x, y, z }
+    }
+
+    fn direction(x: f32, y: f32, z: f32) -> Vector3 {
+        Vector3 { x, y, z }
+    }
+
+    fn block(x: i64, y: i64, z: i64) -> BlockCoordinate {
+        BlockCoordinate { x, y, z }
+    }
+
+    fn cast(origin: WorldCoordinate, direction: Vector3, target: BlockCoordinate) -> RaycastHit {
+        raycast(origin, direction, 16.0, |position| position == target).unwrap()
+    }
+
+    #[test]
+    fn hits_along_each_axis() {
+        let origin = point(0.5, 0.5, 0.5);
+
+        let hit = cast(origin, direction(1.0, 0.0, 0.0), block(3, 0, 0));
+        assert_eq!(hit.face, Face::Left);
+        assert_eq!(hit.distance, 2.5);
+
+        let hit = cast(origin, direction(0.0, 1.0, 0.0), block(0, 3, 0));
+        assert_eq!(hit.face, Face::Bottom);
+        assert_eq!(hit.distance, 2.5);
+
+        let hit = cast(origin, direction(0.0, 0.0, 1.0), block(0, 0, 3));
+        assert_eq!(hit.face, Face::Front);
+        assert_eq!(hit.distance, 2.5);
+    }
+
+    #[test]
+    fn hits_in_negative_directions() {
+        let origin = point(0.5, 0.5, 0.5);
+
+        let hit = cast(origin, direction(-1.0, 0.0, 0.0), block(-3, 0, 0));
+        assert_eq!(hit.face, Face::Right);
+        assert_eq!(hit.distance, 2.5);
+
+        let hit = cast(origin, direction(0.0, -1.0, 0.0), block(0, -3, 0));
+        assert_eq!(hit.face, Face::Top);
+        assert_eq!(hit.distance, 2.5);
+
+        let hit = cast(origin, direction(0.0, 0.0, -1.0), block(0, 0, -3));
+        assert_eq!(hit.face, Face::Back);
+        assert_eq!(hit.distance, 2.5);
+    }
+
+    #[test]
+    fn normal_points_to_the_block_before_the_hit() {
+        let origin = point(0.5, 0.5, 0.5);
+        let directions = [
+            direction(1.0, 0.0, 0.0),
+            direction(-1.0, 0.0, 0.0),
+            direction(0.0, 1.0, 0.0),
+            direction(0.0, -1.0, 0.0),
+            direction(0.0, 0.0, 1.0),
+            direction(0.0, 0.0, -1.0),
+        ];
+
+        for direction in directions.iter() {
+            let target = block(
+                direction.x as i64 * 2,
+                direction.y as i64 * 2,
+                direction.z as i64 * 2,
+            );
+            let hit = cast(origin, *direction, target);
+            let (x, y, z) = hit.face.normal();
+
+            assert_eq!(hit.block, target);
+            assert_eq!(
+                block(target.x + x, target.y + y, target.z + z),
+                block(direction.x as i64, direction.y as i64, direction.z as i64)
+            );
+        }
+    }
+
+    #[test]
+    fn starts_on_a_block_boundary() {
+        let origin = point(1.0, 0.5, 0.5);
+
+        // the block the origin floors to is the starting one, the one behind is right there
+        let hit = cast(origin, direction(-1.0, 0.0, 0.0), block(0, 0, 0));
+        assert_eq!(hit.face, Face::Right);
+        assert_eq!(hit.distance, 0.0);
+
+        let hit = cast(origin, direction(1.0, 0.0, 0.0), block(2, 0, 0));
+        assert_eq!(hit.face, Face::Left);
+        assert_eq!(hit.distance, 1.0);
+
+        let skipped = raycast(origin, direction(1.0, 0.0, 0.0), 16.0, |position| {
+            position == block(1, 0, 0)
+        });
+        assert_eq!(skipped, None);
+    }
+
+    #[test]
+    fn stops_at_the_max_distance() {
+        let origin = point(0.5, 0.5, 0.5);
+        let target = |position: BlockCoordinate| position == block(3, 0, 0);
+
+        assert_eq!(raycast(origin, direction(1.0, 0.0, 0.0), 2.4, target), None);
+        assert!(raycast(origin, direction(1.0, 0.0, 0.0), 2.5, target).is_some());
+    }
+
+    #[test]
+    fn refuses_degenerate_directions() {
+        let origin = point(0.5, 0.5, 0.5);
+
+        assert_eq!(
+            raycast(origin, direction(0.0, 0.0, 0.0), 16.0, |_| true),
+            None
+        );
+        assert_eq!(
+            raycast(origin, direction(f32::NAN, 0.0, 0.0), 16.0, |_| true),
+            None
+        );
+        assert_eq!(
+            raycast(origin, direction(1.0, 0.0, 0.0), f32::NAN, |_| true),
+            None
+        );
+    }
+}
@@ -0,0 +1,102 @@
+/// ticks simulated in a single frame at most, the time beyond is dropped so a long stall does
+/// not take even longer to catch up
+const MAX_TICKS_PER_FRAME: u32 = 10;
+
+/// Splits the time between frames into ticks of a fixed duration
+///
+/// The time left over is carried to the next frame, it tells how far the frame is between the
+/// last two ticks.
+pub struct FixedTimestep {
+    /// in seconds
+    tick_duration: f64,
+    accumulator: f64,
+}
+
+impl FixedTimestep {
+    pub fn new(ticks_per_second: u32) -> Self {
+        Self {
+            tick_duration: 1.0 / ticks_per_second as f64,
+            accumulator: 0.0,
+        }
+    }
+
+    pub fn tick_duration(&self) -> f64 {
+        self.tick_duration
+    }
+
+    /// adds the duration of a frame in seconds, returns the number of ticks to simulate
+    pub fn advance(&mut self, time_delta: f64) -> u32 {
+        self.accumulator += time_delta.max(0.0);
+
+        let ticks = (self.accumulator / self.tick_duration).floor() as u32;
+
+        if ticks > MAX_TICKS_PER_FRAME {
+            let dropped = (ticks - MAX_TICKS_PER_FRAME) as f64 * self.tick_duration;
+            warn!(
+                "<game> running behind, skipping {} ms",
+                (dropped * 1000.0).round()
+            );
+
+            self.accumulator %= self.tick_duration;
+            return MAX_TICKS_PER_FRAME;
+        }
+
+        self.accumulator = (self.accumulator - ticks as f64 * self.tick_duration).max(0.0);
+        ticks
+    }
+
+    /// part of a tick elapsed since the last one, from 0 to 1
+    pub fn alpha(&self) -> f32 {
+        (self.accumulator / self.tick_duration).min(1.0) as f32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_the_ticks_of_a_frame() {
+        let mut timestep = FixedTimestep::new(20);
+
+        assert_eq!(timestep.advance(0.0), 0);
+        assert_eq!(timestep.advance(0.05), 1);
+        assert_eq!(timestep.advance(0.16), 3);
+        // a negative time delta is ignored
+        assert_eq!(timestep.advance(-1.0), 0);
+    }
+
+    #[test]
+    fn carries_the_remainder_to_the_next_frame() {
+        let mut timestep = FixedTimestep::new(20);
+
+        assert_eq!(timestep.advance(0.03), 0);
+        assert_eq!(timestep.advance(0.03), 1);
+        assert!((timestep.accumulator - 0.01).abs() < 1e-9);
+        assert_eq!(timestep.advance(0.045), 1);
+    }
+
+    #[test]
+    fn drops_the_time_beyond_the_ticks_of_a_frame() {
+        let mut timestep = FixedTimestep::new(20);
+
+        assert_eq!(timestep.advance(5.025), MAX_TICKS_PER_FRAME);
+        assert!((timestep.accumulator - 0.025).abs() < 1e-9);
+        assert_eq!(timestep.advance(0.0), 0);
+    }
+
+    #[test]
+    fn tells_how_far_the_frame_is_between_ticks() {
+        let mut timestep = FixedTimestep::new(20);
+        assert_eq!(timestep.alpha(), 0.0);
+
+        timestep.advance(0.0125);
+        assert!((timestep.alpha() - 0.25).abs() < 1e-6);
+
+        timestep.advance(0.05);
+        assert!((timestep.alpha() - 0.25).abs() < 1e-6);
+
+        timestep.advance(0.025);
+        assert!((timestep.alpha() - 0.75).abs() < 1e-6);
+    }
+}
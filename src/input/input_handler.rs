@@ -1,11 +1,13 @@
-use glutin::event::{KeyboardInput, VirtualKeyCode};
+use glutin::event::{ElementState, KeyboardInput, MouseButton, VirtualKeyCode};
 
-use crate::input::{CursorDelta, CursorHandler, KeyboardHandler};
+use crate::input::{CursorDelta, CursorHandler, KeyboardHandler, MouseHandler, TextHandler};
 
 #[derive(Debug, Default)]
 pub struct InputHandler {
     keyboard: KeyboardHandler,
     cursor: CursorHandler,
+    mouse: MouseHandler,
+    text: TextHandler,
 }
 
 impl InputHandler {
@@ -17,6 +19,14 @@ impl InputHandler {
         self.cursor.process(input)
     }
 
+    pub fn process_mouse_button(&mut self, button: MouseButton, state: ElementState) {
+        self.mouse.process(button, state)
+    }
+
+    pub fn process_character(&mut self, c: char) {
+        self.text.process(c)
+    }
+
     pub fn is_key_pressed(&self, keycode: VirtualKeyCode) -> bool {
         self.keyboard.is_pressed(keycode)
     }
@@ -25,6 +35,14 @@ impl InputHandler {
         self.keyboard.just_pressed(keycode)
     }
 
+    pub fn just_clicked(&self, button: MouseButton) -> bool {
+        self.mouse.just_pressed(button)
+    }
+
+    pub fn get_text(&self) -> &str {
+        self.text.get_text()
+    }
+
     pub fn get_cursor_delta(&self) -> &CursorDelta {
         self.cursor.get_delta()
     }
@@ -32,5 +50,7 @@ impl InputHandler {
     pub fn clear(&mut self) {
         self.cursor.clear();
         self.keyboard.clear();
+        self.mouse.clear();
+        self.text.clear();
     }
 }
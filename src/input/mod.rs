@@ -1,8 +1,12 @@
 mod cursor;
 mod input_handler;
 mod keyboard;
+mod mouse;
+mod text;
 
 pub use self::cursor::CursorDelta;
 pub use self::cursor::CursorHandler;
 pub use self::input_handler::InputHandler;
 pub use self::keyboard::KeyboardHandler;
+pub use self::mouse::MouseHandler;
+pub use self::text::TextHandler;
@@ -0,0 +1,39 @@
+use glutin::event::{ElementState, MouseButton};
+use std::collections::HashSet;
+
+#[derive(Debug, Default)]
+pub struct MouseHandler {
+    pressed: HashSet<MouseButton>,
+    pressed_since_clear: HashSet<MouseButton>,
+}
+
+impl MouseHandler {
+    pub fn process(&mut self, button: MouseButton, state: ElementState) {
+        match state {
+            ElementState::Pressed => {
+                if !self.is_pressed(button) {
+                    self.pressed_since_clear.insert(button);
+                }
+                self.pressed.insert(button);
+            }
+            ElementState::Released => {
+                self.pressed.remove(&button);
+            }
+        };
+    }
+
+    /// indicates a button is currently being held
+    pub fn is_pressed(&self, button: MouseButton) -> bool {
+        self.pressed.contains(&button)
+    }
+
+    /// indicates a previously released button was just pressed
+    pub fn just_pressed(&self, button: MouseButton) -> bool {
+        self.pressed_since_clear.contains(&button)
+    }
+
+    /// to call at the end of each gameloop
+    pub fn clear(&mut self) {
+        self.pressed_since_clear.clear();
+    }
+}
@@ -0,0 +1,22 @@
+#[derive(Debug, Default)]
+pub struct TextHandler {
+    text: String,
+}
+
+impl TextHandler {
+    /// control characters like backspace or return are left to the keyboard handler
+    pub fn process(&mut self, c: char) {
+        if !c.is_control() {
+            self.text.push(c);
+        }
+    }
+
+    /// characters typed since the last clear
+    pub fn get_text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn clear(&mut self) {
+        self.text.clear();
+    }
+}
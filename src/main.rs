@@ -2,7 +2,9 @@ mod game;
 mod input;
 mod network;
 mod ops;
+mod options;
 mod render;
+mod server;
 mod utils;
 
 #[macro_use]
@@ -16,22 +18,23 @@ extern crate rusttype;
 extern crate serde;
 extern crate serde_json;
 
-use crate::game::{Game, GameType};
+use crate::game::Game;
 use crate::input::InputHandler;
+use crate::options::FrameLimit;
 use crate::render::display::Display;
 
 use core::utils::{
     logging,
     logging::{FileLogger, FileLoggerOptions, Level, StdoutLogger},
-    sleep,
 };
 use glutin::event::{DeviceEvent, Event, WindowEvent};
 use glutin::event_loop::{ControlFlow, EventLoop};
+use std::env;
 use std::io;
+use std::process;
 use std::time::{Duration, Instant};
 
 const FPS_REFRESH_TIMEOUT: u64 = 1;
-const FRAME_RATE_CAP: u32 = 60;
 const PKG_NAME: &'static str = env!("CARGO_PKG_NAME");
 const PKG_VERSION: &'static str = env!("CARGO_PKG_VERSION");
 
@@ -56,27 +59,38 @@ fn main() -> io::Result<()> {
 
     info!("{} v{}", PKG_NAME, PKG_VERSION);
 
+    let options = match options::from_args(env::args().skip(1)) {
+        Ok(Some(options)) => options,
+        Ok(None) => return Ok(()),
+        Err(err) => {
+            eprintln!("{}", err);
+            process::exit(2);
+        }
+    };
+
     let event_loop = EventLoop::new();
-    let display = Display::new(PKG_NAME, &event_loop);
+    let display = Display::new(
+        PKG_NAME,
+        &event_loop,
+        options.frame_limit == FrameLimit::VSync,
+    );
     let (width, height) = display.size();
 
     let mut input_handler = InputHandler::default();
 
-    #[cfg(not(feature = "remote"))]
-    let game_type = GameType::Local;
-    #[cfg(feature = "remote")]
-    let game_type = GameType::Remote {
-        info: crate::network::RemoteInfo::new(String::from("localhost"), 25565),
-    };
-
-    let mut game = Game::new(game_type)?;
+    let mut game = Game::new(options.game_type, options.username)?;
+    game.set_interpolation_delay(options.interpolation_delay);
     game.resize(width, height);
 
     let mut fps: u32 = 0;
     let mut last_time = Instant::now();
     let mut last_fps_update = Instant::now();
 
-    let expected_tick_duration = Duration::new(1, 0) / FRAME_RATE_CAP;
+    let frame_duration = match options.frame_limit {
+        FrameLimit::Target(rate) => Some(Duration::new(1, 0) / rate),
+        FrameLimit::VSync | FrameLimit::Uncapped => None,
+    };
+    let mut next_frame = Instant::now();
 
     event_loop.run(move |event, _, control_flow| match event {
         Event::DeviceEvent { event, .. } => match event {
@@ -85,6 +99,10 @@ fn main() -> io::Result<()> {
         },
         Event::WindowEvent { event, .. } => match event {
             WindowEvent::KeyboardInput { input, .. } => input_handler.process_keyboard(input),
+            WindowEvent::ReceivedCharacter(c) => input_handler.process_character(c),
+            WindowEvent::MouseInput { state, button, .. } => {
+                input_handler.process_mouse_button(button, state)
+            }
             WindowEvent::Resized(size) => {
                 display.resize(size);
                 game.resize(size.width as usize, size.height as usize);
@@ -100,6 +118,20 @@ fn main() -> io::Result<()> {
             _ => (),
         },
         Event::MainEventsCleared => {
+            if let Some(frame_duration) = frame_duration {
+                let now = Instant::now();
+
+                // events keep being handled while waiting for the next frame
+                if now < next_frame {
+                    *control_flow = ControlFlow::WaitUntil(next_frame);
+                    return;
+                }
+
+                // a late frame does not make the following ones early
+                next_frame = (next_frame + frame_duration).max(now);
+                *control_flow = ControlFlow::WaitUntil(next_frame);
+            }
+
             let time_delta = last_time.elapsed().as_secs_f64();
             last_time = Instant::now();
 
@@ -124,11 +156,6 @@ fn main() -> io::Result<()> {
         Event::RedrawRequested(_) => {
             game.render();
             display.swap_buffers();
-
-            // makeshift fps limiting
-            if let Some(cooldown) = expected_tick_duration.checked_sub(last_time.elapsed()) {
-                sleep(cooldown);
-            }
         }
         Event::LoopDestroyed => return,
         _ => (),
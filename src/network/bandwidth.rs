@@ -0,0 +1,63 @@
+use std::time::{Duration, Instant};
+
+const WINDOW: Duration = Duration::from_secs(1);
+
+/// Bytes sent and received over the last full second, datagrams and stream frames alike
+pub struct Bandwidth {
+    window_start: Instant,
+    sent: usize,
+    received: usize,
+    sent_per_second: usize,
+    received_per_second: usize,
+}
+
+impl Bandwidth {
+    pub fn new() -> Self {
+        Self {
+            window_start: Instant::now(),
+            sent: 0,
+            received: 0,
+            sent_per_second: 0,
+            received_per_second: 0,
+        }
+    }
+
+    pub fn sent(&mut self, bytes: usize, now: Instant) {
+        self.update(now);
+        self.sent += bytes;
+    }
+
+    pub fn received(&mut self, bytes: usize, now: Instant) {
+        self.update(now);
+        self.received += bytes;
+    }
+
+    /// closes the window once a second has passed, a quiet second counts as 0
+    pub fn update(&mut self, now: Instant) {
+        let elapsed = now.duration_since(self.window_start);
+
+        if elapsed < WINDOW {
+            return;
+        }
+
+        if elapsed < WINDOW * 2 {
+            self.sent_per_second = self.sent;
+            self.received_per_second = self.received;
+        } else {
+            self.sent_per_second = 0;
+            self.received_per_second = 0;
+        }
+
+        self.sent = 0;
+        self.received = 0;
+        self.window_start = now;
+    }
+
+    pub fn sent_per_second(&self) -> usize {
+        self.sent_per_second
+    }
+
+    pub fn received_per_second(&self) -> usize {
+        self.received_per_second
+    }
+}
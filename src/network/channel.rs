@@ -0,0 +1,390 @@
+use crate::network::fragment::{self, FragmentGroup, Message, Reassembler};
+use crate::network::packet::{sequence_greater_than, Packet, Sequence};
+
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+
+/// time to wait for an ack before sending a reliable message again
+const RESEND_TIMEOUT: Duration = Duration::from_millis(100);
+/// maximum number of unacknowledged reliable messages in flight
+const WINDOW_SIZE: usize = 512;
+const MAX_ACKS_PER_PACKET: usize = 64;
+/// weight of a new round trip sample in the smoothed round trip time
+const RTT_SMOOTHING: f64 = 0.125;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Delivery {
+    Reliable,
+    Unreliable,
+}
+
+struct PendingMessage {
+    sequence: Sequence,
+    message: Message,
+    last_sent: Option<Instant>,
+    /// the ack of a retransmitted message cannot tell which copy it answers
+    resent: bool,
+}
+
+/// Reliability layer used on top of an unreliable datagram socket
+///
+/// Reliable messages get a sequence number and are retransmitted until the peer acknowledges
+/// them. The peer delivers them exactly once and in order. Unreliable messages have their own
+/// sequence, older ones are dropped when they arrive after a newer one.
+///
+/// Payloads bigger than a datagram are split in fragments, every fragment of a reliable payload
+/// is a reliable message of its own.
+///
+/// The channel never touches a socket, packets are handed back by `poll` and fed through
+/// `receive`, which makes it possible to run two channels against each other in process.
+///
+/// The round trip time is measured from the acks of reliable messages sent only once.
+pub struct Channel {
+    // outgoing
+    next_reliable: Sequence,
+    next_unreliable: Sequence,
+    next_group: FragmentGroup,
+    backlog: VecDeque<Message>,
+    pending: VecDeque<PendingMessage>,
+    outgoing: VecDeque<Packet>,
+    rtt: Option<Duration>,
+
+    // incoming
+    next_expected: Sequence,
+    received: HashMap<Sequence, Message>,
+    latest_unreliable: Option<Sequence>,
+    acks: Vec<Sequence>,
+    reliable_fragments: Reassembler,
+    unreliable_fragments: Reassembler,
+}
+
+impl Channel {
+    pub fn new() -> Self {
+        Self {
+            next_reliable: 0,
+            next_unreliable: 0,
+            next_group: 0,
+            backlog: VecDeque::new(),
+            pending: VecDeque::new(),
+            outgoing: VecDeque::new(),
+            rtt: None,
+
+            next_expected: 0,
+            received: HashMap::new(),
+            latest_unreliable: None,
+            acks: Vec::new(),
+            reliable_fragments: Reassembler::reliable(),
+            unreliable_fragments: Reassembler::unreliable(),
+        }
+    }
+
+    /// smoothed round trip time, none until a reliable message was acknowledged
+    pub fn rtt(&self) -> Option<Duration> {
+        self.rtt
+    }
+
+    pub fn send(&mut self, payload: Vec<u8>, delivery: Delivery) {
+        let group = match delivery {
+            Delivery::Reliable => self.next_group,
+            Delivery::Unreliable => self.next_unreliable,
+        };
+
+        let messages = match fragment::split(payload, group) {
+            Some(messages) => messages,
+            None => {
+                error!(
+                    "<network> payload is bigger than {} bytes",
+                    fragment::MAX_PAYLOAD_SIZE
+                );
+                return;
+            }
+        };
+
+        match delivery {
+            Delivery::Reliable => {
+                self.next_group = self.next_group.wrapping_add(1);
+                self.backlog.extend(messages);
+            }
+            Delivery::Unreliable => {
+                for message in messages {
+                    self.outgoing.push_back(Packet::Unreliable {
+                        sequence: self.next_unreliable,
+                        message,
+                    });
+                }
+                self.next_unreliable = self.next_unreliable.wrapping_add(1);
+            }
+        }
+    }
+
+    /// handles an incoming packet and returns the payloads that are ready to be delivered
+    pub fn receive(&mut self, packet: Packet, now: Instant) -> Vec<Vec<u8>> {
+        let mut delivered = Vec::new();
+
+        match packet {
+            Packet::Reliable { sequence, message } => {
+                let offset = sequence.wrapping_sub(self.next_expected) as usize;
+
+                if !sequence_greater_than(sequence, self.next_expected.wrapping_sub(1)) {
+                    // already delivered, the ack was probably lost
+                    self.acks.push(sequence);
+                } else if offset < WINDOW_SIZE {
+                    self.acks.push(sequence);
+                    self.received.entry(sequence).or_insert(message);
+
+                    while let Some(message) = self.received.remove(&self.next_expected) {
+                        self.next_expected = self.next_expected.wrapping_add(1);
+
+                        if let Some(payload) = self.reliable_fragments.add(message, now) {
+                            delivered.push(payload);
+                        }
+                    }
+                }
+            }
+            Packet::Unreliable { sequence, message } => {
+                let fragment = match message {
+                    Message::Fragment { .. } => true,
+                    Message::Whole(_) => false,
+                };
+
+                // fragments of the latest message are still expected
+                let stale = self.latest_unreliable.map_or(false, |latest| {
+                    if fragment {
+                        sequence_greater_than(latest, sequence)
+                    } else {
+                        !sequence_greater_than(sequence, latest)
+                    }
+                });
+
+                if !stale {
+                    self.latest_unreliable = Some(sequence);
+
+                    if let Some(payload) = self.unreliable_fragments.add(message, now) {
+                        delivered.push(payload);
+                    }
+                }
+            }
+            Packet::Ack { sequences } => {
+                let samples = self
+                    .pending
+                    .iter()
+                    .filter(|message| !message.resent && sequences.contains(&message.sequence))
+                    .filter_map(|message| message.last_sent)
+                    .map(|last_sent| now.duration_since(last_sent))
+                    .collect::<Vec<Duration>>();
+
+                for sample in samples {
+                    self.rtt = Some(match self.rtt {
+                        Some(rtt) => {
+                            rtt.mul_f64(1.0 - RTT_SMOOTHING) + sample.mul_f64(RTT_SMOOTHING)
+                        }
+                        None => sample,
+                    });
+                }
+
+                self.pending
+                    .retain(|message| !sequences.contains(&message.sequence));
+            }
+            // the handshake, heartbeats, time sync and encryption are handled by the connection
+            _ => {}
+        }
+
+        delivered
+    }
+
+    /// returns every packet that should be sent right now: acks, new messages and retransmissions
+    pub fn poll(&mut self, now: Instant) -> Vec<Packet> {
+        let mut packets = Vec::new();
+
+        for sequences in self.acks.chunks(MAX_ACKS_PER_PACKET) {
+            packets.push(Packet::Ack {
+                sequences: sequences.to_vec(),
+            });
+        }
+        self.acks.clear();
+
+        packets.extend(self.outgoing.drain(..));
+
+        // reliable fragments are acked and delivered in order, their message always completes and
+        // dropping it would lose it for good
+        self.unreliable_fragments.expire(now);
+
+        while self.pending.len() < WINDOW_SIZE {
+            if let Some(message) = self.backlog.pop_front() {
+                self.pending.push_back(PendingMessage {
+                    sequence: self.next_reliable,
+                    message,
+                    last_sent: None,
+                    resent: false,
+                });
+                self.next_reliable = self.next_reliable.wrapping_add(1);
+            } else {
+                break;
+            }
+        }
+
+        for message in self.pending.iter_mut() {
+            let due = message.last_sent.map_or(true, |last_sent| {
+                now.duration_since(last_sent) >= RESEND_TIMEOUT
+            });
+
+            if due {
+                packets.push(Packet::Reliable {
+                    sequence: message.sequence,
+                    message: message.message.clone(),
+                });
+                message.resent = message.last_sent.is_some();
+                message.last_sent = Some(now);
+            }
+        }
+
+        packets
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::Random;
+
+    /// loses, duplicates and shuffles the packets it carries
+    struct Network {
+        random: Random,
+    }
+
+    impl Network {
+        fn new(seed: u64) -> Self {
+            Self {
+                random: Random::new(seed),
+            }
+        }
+
+        fn carry(&mut self, packets: Vec<Packet>) -> Vec<Packet> {
+            let mut carried = Vec::new();
+
+            for packet in packets {
+                if self.random.chance(0.3) {
+                    continue;
+                }
+
+                if self.random.chance(0.1) {
+                    carried.push(packet.clone());
+                }
+
+                carried.push(packet);
+            }
+
+            for i in (1..carried.len()).rev() {
+                let j = self.random.range(0, i as i64) as usize;
+                carried.swap(i, j);
+            }
+
+            carried
+        }
+    }
+
+    fn payload(index: usize) -> Vec<u8> {
+        // every tenth payload needs several fragments
+        let size = if index % 10 == 0 {
+            fragment::MAX_FRAGMENT_SIZE * 3
+        } else {
+            8
+        };
+
+        (0..size).map(|byte| (byte + index) as u8).collect()
+    }
+
+    /// sends `count` reliable payloads from `sender` to `receiver` over a bad network
+    fn transfer(sender: &mut Channel, receiver: &mut Channel, count: usize, seed: u64) {
+        let mut network = Network::new(seed);
+        let mut now = Instant::now();
+        let mut delivered = Vec::new();
+
+        for index in 0..count {
+            sender.send(payload(index), Delivery::Reliable);
+        }
+
+        for _ in 0..1000 {
+            for packet in network.carry(sender.poll(now)) {
+                delivered.extend(receiver.receive(packet, now));
+            }
+
+            for packet in network.carry(receiver.poll(now)) {
+                sender.receive(packet, now);
+            }
+
+            if delivered.len() == count && sender.pending.is_empty() {
+                break;
+            }
+
+            now += RESEND_TIMEOUT;
+        }
+
+        let expected = (0..count).map(payload).collect::<Vec<Vec<u8>>>();
+        assert_eq!(delivered, expected);
+        assert!(sender.pending.is_empty());
+    }
+
+    #[test]
+    fn delivers_reliable_messages_once_and_in_order() {
+        transfer(&mut Channel::new(), &mut Channel::new(), 100, 1);
+    }
+
+    #[test]
+    fn delivers_reliable_messages_across_the_sequence_wraparound() {
+        let mut sender = Channel::new();
+        let mut receiver = Channel::new();
+        sender.next_reliable = Sequence::MAX - 20;
+        receiver.next_expected = Sequence::MAX - 20;
+
+        transfer(&mut sender, &mut receiver, 50, 2);
+        assert!(sender.next_reliable < 100);
+    }
+
+    #[test]
+    fn drops_stale_unreliable_messages() {
+        let mut sender = Channel::new();
+        let mut receiver = Channel::new();
+        let now = Instant::now();
+
+        for index in 0..3 {
+            sender.send(vec![index], Delivery::Unreliable);
+        }
+
+        let mut delivered = Vec::new();
+
+        for packet in sender.poll(now).into_iter().rev() {
+            delivered.extend(receiver.receive(packet, now));
+        }
+
+        assert_eq!(delivered, vec![vec![2]]);
+    }
+
+    #[test]
+    fn orders_unreliable_messages_across_the_sequence_wraparound() {
+        let mut sender = Channel::new();
+        let mut receiver = Channel::new();
+        let now = Instant::now();
+        sender.next_unreliable = Sequence::MAX;
+
+        let mut delivered = Vec::new();
+
+        for index in 0..2 {
+            sender.send(vec![index], Delivery::Unreliable);
+
+            for packet in sender.poll(now) {
+                delivered.extend(receiver.receive(packet, now));
+            }
+        }
+
+        // the message sent before the wraparound is older than the one after
+        sender.next_unreliable = Sequence::MAX;
+        sender.send(vec![2], Delivery::Unreliable);
+
+        for packet in sender.poll(now) {
+            delivered.extend(receiver.receive(packet, now));
+        }
+
+        assert_eq!(delivered, vec![vec![0], vec![1]]);
+    }
+}
@@ -0,0 +1,110 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// milliseconds on the server clock
+pub type ServerTime = u64;
+
+/// samples kept to pick the offset from
+const MAX_SAMPLES: usize = 8;
+/// samples needed before the estimate is trusted and requests slow down
+const SYNCHRONIZED_SAMPLES: usize = 4;
+/// weight of a new sample in the smoothed round trip time
+const RTT_SMOOTHING: f64 = 0.125;
+
+/// Offset between the clocks measured by one request and its response
+#[derive(Debug, Copy, Clone)]
+struct TimeSample {
+    offset: i64,
+    rtt: i64,
+}
+
+/// Estimates the server clock the way NTP does
+///
+/// The client stamps a request with its local time, the server answers with the times it received
+/// and sent the response. Assuming both trips take as long, the offset between the clocks is the
+/// average of the two one way differences. The sample with the shortest round trip suffered the
+/// least queuing, its offset is the one used. Until then the local clock is used.
+#[derive(Debug, Clone)]
+pub struct ServerClock {
+    start: Instant,
+    offset: Option<i64>,
+    samples: VecDeque<TimeSample>,
+    rtt: Option<Duration>,
+}
+
+impl ServerClock {
+    pub fn new() -> Self {
+        Self {
+            start: Instant::now(),
+            offset: None,
+            samples: VecDeque::new(),
+            rtt: None,
+        }
+    }
+
+    /// milliseconds on the local clock, what time requests are stamped with
+    pub fn local_time(&self) -> u64 {
+        self.start.elapsed().as_millis() as u64
+    }
+
+    /// handles the response to a request stamped with `request_time`, the server received it at
+    /// `receive_time` and answered at `send_time`
+    pub fn synchronize(
+        &mut self,
+        request_time: u64,
+        receive_time: ServerTime,
+        send_time: ServerTime,
+    ) {
+        let response_time = self.local_time() as i64;
+        let request_time = request_time as i64;
+        let receive_time = receive_time as i64;
+        let send_time = send_time as i64;
+
+        if request_time > response_time || receive_time > send_time {
+            warn!("<network> inconsistent time response");
+            return;
+        }
+
+        let sample = TimeSample {
+            offset: ((receive_time - request_time) + (send_time - response_time)) / 2,
+            rtt: ((response_time - request_time) - (send_time - receive_time)).max(0),
+        };
+
+        self.samples.push_back(sample);
+
+        if self.samples.len() > MAX_SAMPLES {
+            self.samples.pop_front();
+        }
+
+        self.offset = self
+            .samples
+            .iter()
+            .min_by_key(|sample| sample.rtt)
+            .map(|sample| sample.offset);
+
+        let rtt = Duration::from_millis(sample.rtt as u64);
+        self.rtt = Some(match self.rtt {
+            Some(smoothed) => smoothed.mul_f64(1.0 - RTT_SMOOTHING) + rtt.mul_f64(RTT_SMOOTHING),
+            None => rtt,
+        });
+    }
+
+    /// enough requests were answered to trust the estimate
+    pub fn is_synchronized(&self) -> bool {
+        self.samples.len() >= SYNCHRONIZED_SAMPLES
+    }
+
+    pub fn now(&self) -> ServerTime {
+        (self.local_time() as i64 + self.offset.unwrap_or(0)).max(0) as ServerTime
+    }
+
+    /// server time minus local time in milliseconds, none until estimated
+    pub fn offset(&self) -> Option<i64> {
+        self.offset
+    }
+
+    /// smoothed round trip time, none until a request was answered
+    pub fn rtt(&self) -> Option<Duration> {
+        self.rtt
+    }
+}
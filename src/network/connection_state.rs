@@ -0,0 +1,31 @@
+use crate::network::packet::RejectReason;
+
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConnectionState {
+    /// waiting for the server to answer the handshake
+    Connecting,
+    Connected,
+    /// the connection was lost, trying to resume the session
+    Reconnecting {
+        attempt: u32,
+    },
+    /// the server did not answer the handshake or stopped sending heartbeats
+    TimedOut,
+    Rejected(RejectReason),
+}
+
+impl fmt::Display for ConnectionState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConnectionState::Connecting => write!(f, "Connecting to the server..."),
+            ConnectionState::Connected => write!(f, "Connected"),
+            ConnectionState::Reconnecting { attempt } => {
+                write!(f, "Connection lost, reconnecting (attempt {})...", attempt)
+            }
+            ConnectionState::TimedOut => write!(f, "Connection timed out"),
+            ConnectionState::Rejected(reason) => write!(f, "Connection refused: {}", reason),
+        }
+    }
+}
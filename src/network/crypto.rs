@@ -0,0 +1,324 @@
+use crate::network::packet::Packet;
+
+use chacha20poly1305::aead::{Aead, NewAead};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use hkdf::Hkdf;
+use hmac::{Hmac, Mac, NewMac};
+use rand_core::OsRng;
+use sha2::Sha256;
+use x25519_dalek::{PublicKey as DalekPublicKey, StaticSecret};
+
+/// x25519 public key
+pub type PublicKey = [u8; 32];
+/// proves the knowledge of a session token without revealing it
+pub type ResumeProof = [u8; 32];
+
+const KEY_DERIVATION_SALT: &'static [u8] = b"roadrunner session";
+const CLIENT_TO_SERVER: &'static [u8] = b"client to server";
+const SERVER_TO_CLIENT: &'static [u8] = b"server to client";
+/// counters older than this many packets behind the latest are refused
+const REPLAY_WINDOW_SIZE: u64 = 64;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Role {
+    Client,
+    Server,
+}
+
+/// Ephemeral key pair of one side of a handshake
+///
+/// The client keeps the same pair while it retries a handshake so the server can answer any of
+/// its attempts, a new pair is made for every new handshake.
+pub struct KeyExchange {
+    secret: StaticSecret,
+    public: PublicKey,
+}
+
+impl KeyExchange {
+    pub fn new() -> Self {
+        let secret = StaticSecret::new(OsRng);
+        let public = *DalekPublicKey::from(&secret).as_bytes();
+
+        Self { secret, public }
+    }
+
+    pub fn public_key(&self) -> PublicKey {
+        self.public
+    }
+
+    /// derives the keys of both directions from the shared secret, none when the peer sent a key
+    /// that does not contribute to it
+    pub fn complete(&self, peer: &PublicKey, role: Role) -> Option<SessionCipher> {
+        let shared = self.secret.diffie_hellman(&DalekPublicKey::from(*peer));
+
+        if shared.as_bytes().iter().all(|byte| *byte == 0) {
+            return None;
+        }
+
+        let (client_key, server_key) = match role {
+            Role::Client => (&self.public, peer),
+            Role::Server => (peer, &self.public),
+        };
+
+        let mut salt = KEY_DERIVATION_SALT.to_vec();
+        salt.extend_from_slice(client_key);
+        salt.extend_from_slice(server_key);
+
+        let hkdf = Hkdf::<Sha256>::new(Some(&salt), shared.as_bytes());
+        let mut client_to_server = [0u8; 32];
+        let mut server_to_client = [0u8; 32];
+        hkdf.expand(CLIENT_TO_SERVER, &mut client_to_server).ok()?;
+        hkdf.expand(SERVER_TO_CLIENT, &mut server_to_client).ok()?;
+
+        let (send_key, receive_key) = match role {
+            Role::Client => (client_to_server, server_to_client),
+            Role::Server => (server_to_client, client_to_server),
+        };
+
+        Some(SessionCipher {
+            send: ChaCha20Poly1305::new(Key::from_slice(&send_key)),
+            receive: ChaCha20Poly1305::new(Key::from_slice(&receive_key)),
+            next_counter: 0,
+            replay: ReplayWindow::new(),
+        })
+    }
+}
+
+/// Session token of a lost connection, keyed to the public key of the new handshake so an
+/// observer cannot reuse it
+pub fn resume_proof(token: u64, key: &PublicKey) -> ResumeProof {
+    let mut mac = Hmac::<Sha256>::new_varkey(&token.to_le_bytes()).unwrap();
+    mac.update(key);
+
+    let mut proof = [0u8; 32];
+    proof.copy_from_slice(&mac.finalize().into_bytes());
+    proof
+}
+
+pub fn verify_resume_proof(token: u64, key: &PublicKey, proof: &ResumeProof) -> bool {
+    let mut mac = Hmac::<Sha256>::new_varkey(&token.to_le_bytes()).unwrap();
+    mac.update(key);
+    mac.verify(proof).is_ok()
+}
+
+/// Authenticated encryption of the packets of a session
+///
+/// Each direction has its own key, so the counter of a packet is enough to make its nonce
+/// unique. The counter is sent in clear, packets that were already received or that fall too far
+/// behind are dropped before being decrypted.
+pub struct SessionCipher {
+    send: ChaCha20Poly1305,
+    receive: ChaCha20Poly1305,
+    next_counter: u64,
+    replay: ReplayWindow,
+}
+
+impl SessionCipher {
+    pub fn seal(&mut self, packet: &Packet) -> Option<Packet> {
+        let plaintext = bincode::serialize(packet).ok()?;
+        let counter = self.next_counter;
+
+        let data = self
+            .send
+            .encrypt(&nonce(counter), plaintext.as_slice())
+            .ok()?;
+        self.next_counter += 1;
+
+        Some(Packet::Sealed { counter, data })
+    }
+
+    /// none when the packet is replayed, forged or corrupted
+    pub fn open(&mut self, counter: u64, data: &[u8]) -> Option<Packet> {
+        if !self.replay.is_fresh(counter) {
+            return None;
+        }
+
+        let plaintext = self.receive.decrypt(&nonce(counter), data).ok()?;
+
+        // only authentic packets move the window, forged ones could otherwise push it forward
+        self.replay.mark(counter);
+
+        bincode::deserialize(&plaintext).ok()
+    }
+}
+
+fn nonce(counter: u64) -> Nonce {
+    let mut nonce = [0u8; 12];
+    nonce[4..].copy_from_slice(&counter.to_le_bytes());
+
+    *Nonce::from_slice(&nonce)
+}
+
+/// Counters received recently, as a bitmap of the ones right behind the latest
+struct ReplayWindow {
+    latest: Option<u64>,
+    seen: u64,
+}
+
+impl ReplayWindow {
+    fn new() -> Self {
+        Self {
+            latest: None,
+            seen: 0,
+        }
+    }
+
+    fn is_fresh(&self, counter: u64) -> bool {
+        let latest = match self.latest {
+            Some(latest) => latest,
+            None => return true,
+        };
+
+        if counter > latest {
+            return true;
+        }
+
+        let behind = latest - counter;
+        behind < REPLAY_WINDOW_SIZE && self.seen & (1 << behind) == 0
+    }
+
+    fn mark(&mut self, counter: u64) {
+        match self.latest {
+            Some(latest) if counter <= latest => self.seen |= 1 << (latest - counter),
+            Some(latest) => {
+                let ahead = counter - latest;
+
+                self.seen = if ahead < REPLAY_WINDOW_SIZE {
+                    (self.seen << ahead) | 1
+                } else {
+                    1
+                };
+                self.latest = Some(counter);
+            }
+            None => {
+                self.seen = 1;
+                self.latest = Some(counter);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ciphers() -> (SessionCipher, SessionCipher) {
+        let client = KeyExchange::new();
+        let server = KeyExchange::new();
+
+        (
+            client.complete(&server.public_key(), Role::Client).unwrap(),
+            server.complete(&client.public_key(), Role::Server).unwrap(),
+        )
+    }
+
+    fn sealed(cipher: &mut SessionCipher, client_time: u64) -> (u64, Vec<u8>) {
+        match cipher.seal(&Packet::TimeRequest { client_time }) {
+            Some(Packet::Sealed { counter, data }) => (counter, data),
+            packet => panic!("unexpected packet {:?}", packet),
+        }
+    }
+
+    #[test]
+    fn refuses_duplicate_counters() {
+        let mut window = ReplayWindow::new();
+
+        assert!(window.is_fresh(5));
+        window.mark(5);
+        assert!(!window.is_fresh(5));
+
+        window.mark(6);
+        assert!(!window.is_fresh(5));
+        assert!(!window.is_fresh(6));
+    }
+
+    #[test]
+    fn refuses_counters_behind_the_window() {
+        let mut window = ReplayWindow::new();
+        window.mark(100);
+
+        assert!(window.is_fresh(100 - (REPLAY_WINDOW_SIZE - 1)));
+        assert!(!window.is_fresh(100 - REPLAY_WINDOW_SIZE));
+    }
+
+    #[test]
+    fn accepts_counters_out_of_order_within_the_window() {
+        let mut window = ReplayWindow::new();
+
+        for counter in [10, 7, 9, 3, 8].iter() {
+            assert!(window.is_fresh(*counter));
+            window.mark(*counter);
+        }
+
+        for counter in [3, 7, 8, 9, 10].iter() {
+            assert!(!window.is_fresh(*counter));
+        }
+
+        assert!(window.is_fresh(4));
+        assert!(window.is_fresh(11));
+    }
+
+    #[test]
+    fn forgets_the_window_after_a_long_jump() {
+        let mut window = ReplayWindow::new();
+        window.mark(10);
+        window.mark(9);
+        window.mark(10 + REPLAY_WINDOW_SIZE);
+
+        assert_eq!(window.seen, 1);
+        assert!(!window.is_fresh(10 + REPLAY_WINDOW_SIZE));
+        assert!(!window.is_fresh(10));
+        assert!(window.is_fresh(11));
+        assert!(window.is_fresh(10 + REPLAY_WINDOW_SIZE - 1));
+
+        // a jump just short of the window keeps the oldest counter in it
+        let mut window = ReplayWindow::new();
+        window.mark(10);
+        window.mark(10 + REPLAY_WINDOW_SIZE - 1);
+
+        assert!(!window.is_fresh(10));
+        assert!(window.is_fresh(11));
+    }
+
+    #[test]
+    fn opens_what_the_peer_sealed() {
+        let (mut client, mut server) = ciphers();
+        let (counter, data) = sealed(&mut client, 42);
+
+        assert!(matches!(
+            server.open(counter, &data),
+            Some(Packet::TimeRequest { client_time: 42 })
+        ));
+        assert_ne!(sealed(&mut client, 42).0, counter);
+    }
+
+    #[test]
+    fn refuses_tampered_packets_without_moving_the_window() {
+        let (mut client, mut server) = ciphers();
+        let (counter, data) = sealed(&mut client, 42);
+
+        let mut tampered = data.clone();
+        tampered[0] ^= 1;
+
+        assert!(server.open(counter, &tampered).is_none());
+        // a forged counter far ahead must not push the window past the genuine packets
+        assert!(server.open(counter + 1000, &data).is_none());
+        assert_eq!(server.replay.latest, None);
+        assert_eq!(server.replay.seen, 0);
+
+        assert!(server.open(counter, &data).is_some());
+    }
+
+    #[test]
+    fn refuses_replayed_packets() {
+        let (mut client, mut server) = ciphers();
+        let (counter, data) = sealed(&mut client, 42);
+
+        assert!(server.open(counter, &data).is_some());
+        let (latest, seen) = (server.replay.latest, server.replay.seen);
+
+        assert!(server.open(counter, &data).is_none());
+        assert_eq!(server.replay.latest, latest);
+        assert_eq!(server.replay.seen, seen);
+    }
+}
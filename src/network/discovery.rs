@@ -0,0 +1,241 @@
+use crate::network::packet::PROTOCOL_VERSION;
+use crate::network::RemoteInfo;
+
+use serde::{Deserialize, Serialize};
+use std::io;
+use std::net::{Ipv4Addr, SocketAddr, UdpSocket};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// port servers listen to for discovery queries
+pub const DISCOVERY_PORT: u16 = 25566;
+const MAX_DATAGRAM_SIZE: usize = 1024;
+/// protects against unrelated traffic on the discovery port
+const MAGIC: [u8; 4] = *b"RRDS";
+
+#[derive(Serialize, Deserialize)]
+enum DiscoveryPacket {
+    Query {
+        magic: [u8; 4],
+        nonce: u64,
+    },
+    Response {
+        magic: [u8; 4],
+        nonce: u64,
+        description: ServerDescription,
+    },
+}
+
+/// What a server tells about itself when asked
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ServerDescription {
+    pub name: String,
+    pub players: u32,
+    pub max_players: u32,
+    pub protocol_version: u32,
+    /// port of the game server, not necessarily the discovery port
+    pub port: u16,
+}
+
+/// Server that answered a discovery query
+#[derive(Debug, Clone)]
+pub struct ServerListing {
+    pub info: RemoteInfo,
+    pub description: ServerDescription,
+    pub latency: Duration,
+}
+
+impl ServerListing {
+    /// the client could not talk to a server running another version
+    pub fn is_compatible(&self) -> bool {
+        self.description.protocol_version == PROTOCOL_VERSION
+    }
+}
+
+/// Looks for servers by sending a query and collecting the answers
+pub struct Discovery {
+    socket: UdpSocket,
+    nonce: u64,
+    sent_at: Instant,
+    servers: Vec<ServerListing>,
+}
+
+impl Discovery {
+    /// queries every server of the local network
+    pub fn broadcast() -> io::Result<Self> {
+        Self::query(SocketAddr::from((Ipv4Addr::BROADCAST, DISCOVERY_PORT)))
+    }
+
+    /// queries a single address, a responder on loopback for instance
+    pub fn query(target: SocketAddr) -> io::Result<Self> {
+        let socket = UdpSocket::bind("0.0.0.0:0")?;
+        socket.set_broadcast(true)?;
+        socket.set_nonblocking(true)?;
+
+        // tells our answers apart from the ones to other clients of the network
+        let nonce = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |time| time.as_nanos() as u64)
+            ^ u64::from(std::process::id());
+        let query = DiscoveryPacket::Query {
+            magic: MAGIC,
+            nonce,
+        };
+
+        socket.send_to(&encode(&query)?, target)?;
+        let sent_at = Instant::now();
+
+        Ok(Self {
+            socket,
+            nonce,
+            sent_at,
+            servers: Vec::new(),
+        })
+    }
+
+    /// collects the answers received so far
+    pub fn poll(&mut self) -> &[ServerListing] {
+        let mut data = [0; MAX_DATAGRAM_SIZE];
+
+        loop {
+            let (size, source) = match self.socket.recv_from(&mut data) {
+                Ok(received) => received,
+                Err(_) => break,
+            };
+
+            let description = match bincode::deserialize(&data[..size]) {
+                Ok(DiscoveryPacket::Response {
+                    magic,
+                    nonce,
+                    description,
+                }) if magic == MAGIC && nonce == self.nonce => description,
+                _ => continue,
+            };
+
+            let info = RemoteInfo::new(source.ip().to_string(), description.port);
+
+            // broadcasts can reach a server through several interfaces
+            if self.servers.iter().any(|server| server.info == info) {
+                continue;
+            }
+
+            info!(
+                "<discovery> found \"{}\" at {}",
+                description.name,
+                info.address()
+            );
+
+            self.servers.push(ServerListing {
+                info,
+                description,
+                latency: self.sent_at.elapsed(),
+            });
+        }
+
+        &self.servers
+    }
+
+    /// waits `timeout` for the answers
+    pub fn wait(mut self, timeout: Duration) -> Vec<ServerListing> {
+        while self.sent_at.elapsed() < timeout {
+            self.poll();
+            std::thread::sleep(Duration::from_millis(10));
+        }
+
+        self.poll();
+        self.servers
+    }
+}
+
+/// Server side of the discovery, answers the queries with the server description
+pub struct DiscoveryResponder {
+    socket: UdpSocket,
+    pub description: ServerDescription,
+}
+
+impl DiscoveryResponder {
+    pub fn bind(address: SocketAddr, description: ServerDescription) -> io::Result<Self> {
+        let socket = UdpSocket::bind(address)?;
+        socket.set_nonblocking(true)?;
+
+        Ok(Self {
+            socket,
+            description,
+        })
+    }
+
+    /// address the responder listens on, lets tests bind to any port of loopback
+    #[cfg(test)]
+    pub fn local_address(&self) -> io::Result<SocketAddr> {
+        self.socket.local_addr()
+    }
+
+    /// answers the pending queries
+    pub fn poll(&mut self) {
+        let mut data = [0; MAX_DATAGRAM_SIZE];
+
+        loop {
+            let (size, source) = match self.socket.recv_from(&mut data) {
+                Ok(received) => received,
+                Err(_) => break,
+            };
+
+            let nonce = match bincode::deserialize(&data[..size]) {
+                Ok(DiscoveryPacket::Query { magic, nonce }) if magic == MAGIC => nonce,
+                _ => continue,
+            };
+
+            let response = DiscoveryPacket::Response {
+                magic: MAGIC,
+                nonce,
+                description: self.description.clone(),
+            };
+
+            if let Ok(buffer) = encode(&response) {
+                let _ = self.socket.send_to(&buffer, source);
+            }
+        }
+    }
+}
+
+fn encode(packet: &DiscoveryPacket) -> io::Result<Vec<u8>> {
+    bincode::serialize(packet).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn description() -> ServerDescription {
+        ServerDescription {
+            name: String::from("test"),
+            players: 1,
+            max_players: 8,
+            protocol_version: PROTOCOL_VERSION,
+            port: 25565,
+        }
+    }
+
+    #[test]
+    fn finds_a_responder_on_loopback() {
+        let mut responder =
+            DiscoveryResponder::bind(SocketAddr::from((Ipv4Addr::LOCALHOST, 0)), description())
+                .unwrap();
+        let mut discovery = Discovery::query(responder.local_address().unwrap()).unwrap();
+        let deadline = Instant::now() + Duration::from_secs(2);
+
+        while discovery.poll().is_empty() && Instant::now() < deadline {
+            responder.poll();
+            std::thread::sleep(Duration::from_millis(5));
+        }
+
+        let servers = discovery.poll();
+
+        assert_eq!(servers.len(), 1);
+        assert_eq!(servers[0].description, description());
+        assert_eq!(
+            servers[0].info,
+            RemoteInfo::new(String::from("127.0.0.1"), 25565)
+        );
+        assert!(servers[0].is_compatible());
+    }
+}
@@ -0,0 +1,299 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// biggest slice of a payload sent in one datagram, keeps packets under the usual MTU
+pub const MAX_FRAGMENT_SIZE: usize = 1024;
+/// biggest payload that can be sent, larger ones are refused on both ends
+pub const MAX_PAYLOAD_SIZE: usize = MAX_FRAGMENT_SIZE * MAX_FRAGMENT_COUNT;
+const MAX_FRAGMENT_COUNT: usize = 1024;
+/// number of messages that can be reassembled at the same time
+const MAX_PARTIAL_MESSAGES: usize = 64;
+/// time after which an incomplete message is discarded
+const REASSEMBLY_TIMEOUT: Duration = Duration::from_secs(5);
+
+pub type FragmentGroup = u16;
+
+/// Unit carried by a packet, either a complete payload or a slice of a larger one
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Message {
+    Whole(Vec<u8>),
+    Fragment {
+        group: FragmentGroup,
+        index: u16,
+        count: u16,
+        data: Vec<u8>,
+    },
+}
+
+/// splits a payload into messages that each fit in a datagram
+pub fn split(payload: Vec<u8>, group: FragmentGroup) -> Option<Vec<Message>> {
+    if payload.len() <= MAX_FRAGMENT_SIZE {
+        return Some(vec![Message::Whole(payload)]);
+    }
+
+    if payload.len() > MAX_PAYLOAD_SIZE {
+        return None;
+    }
+
+    let count = ((payload.len() + MAX_FRAGMENT_SIZE - 1) / MAX_FRAGMENT_SIZE) as u16;
+
+    Some(
+        payload
+            .chunks(MAX_FRAGMENT_SIZE)
+            .enumerate()
+            .map(|(index, data)| Message::Fragment {
+                group,
+                index: index as u16,
+                count,
+                data: data.to_vec(),
+            })
+            .collect(),
+    )
+}
+
+struct PartialMessage {
+    fragments: Vec<Option<Vec<u8>>>,
+    received: usize,
+    started: Instant,
+}
+
+/// Collects fragments until their message is complete
+pub struct Reassembler {
+    partials: HashMap<FragmentGroup, PartialMessage>,
+    /// the fragments of a reliable message were acked, it is never dropped since they would not
+    /// be sent again
+    reliable: bool,
+}
+
+impl Reassembler {
+    pub fn reliable() -> Self {
+        Self {
+            partials: HashMap::new(),
+            reliable: true,
+        }
+    }
+
+    pub fn unreliable() -> Self {
+        Self {
+            partials: HashMap::new(),
+            reliable: false,
+        }
+    }
+
+    /// returns the complete payload once every fragment of its group was received
+    pub fn add(&mut self, message: Message, now: Instant) -> Option<Vec<u8>> {
+        let (group, index, count, data) = match message {
+            Message::Whole(payload) => return Some(payload),
+            Message::Fragment {
+                group,
+                index,
+                count,
+                data,
+            } => (group, index as usize, count as usize, data),
+        };
+
+        if count == 0
+            || count > MAX_FRAGMENT_COUNT
+            || index >= count
+            || data.len() > MAX_FRAGMENT_SIZE
+        {
+            warn!("<network> dropping malformed fragment of group {}", group);
+            return None;
+        }
+
+        // reliable fragments come in order, so only one of their messages is partial at a time
+        let full = self.partials.len() >= MAX_PARTIAL_MESSAGES;
+
+        if full && !self.reliable && !self.partials.contains_key(&group) {
+            self.evict_oldest();
+        }
+
+        let partial = self
+            .partials
+            .entry(group)
+            .or_insert_with(|| PartialMessage {
+                fragments: vec![None; count],
+                received: 0,
+                started: now,
+            });
+
+        if partial.fragments.len() != count {
+            warn!("<network> fragment count mismatch in group {}", group);
+            self.partials.remove(&group);
+            return None;
+        }
+
+        if partial.fragments[index].is_none() {
+            partial.fragments[index] = Some(data);
+            partial.received += 1;
+        }
+
+        if partial.received < count {
+            return None;
+        }
+
+        self.partials.remove(&group).map(|partial| {
+            partial
+                .fragments
+                .into_iter()
+                .flat_map(|fragment| fragment.unwrap())
+                .collect()
+        })
+    }
+
+    /// drops the messages that waited too long for their missing fragments
+    pub fn expire(&mut self, now: Instant) {
+        if self.reliable {
+            return;
+        }
+
+        self.partials.retain(|group, partial| {
+            let alive = now.duration_since(partial.started) < REASSEMBLY_TIMEOUT;
+
+            if !alive {
+                warn!(
+                    "<network> discarding incomplete message {} ({}/{} fragments)",
+                    group,
+                    partial.received,
+                    partial.fragments.len()
+                );
+            }
+
+            alive
+        });
+    }
+
+    fn evict_oldest(&mut self) {
+        let oldest = self
+            .partials
+            .iter()
+            .min_by_key(|(_, partial)| partial.started)
+            .map(|(group, _)| *group);
+
+        if let Some(group) = oldest {
+            warn!("<network> too many partial messages, discarding {}", group);
+            self.partials.remove(&group);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fragment(group: FragmentGroup, index: u16, count: u16) -> Message {
+        Message::Fragment {
+            group,
+            index,
+            count,
+            data: vec![index as u8],
+        }
+    }
+
+    fn payload() -> Vec<u8> {
+        (0..MAX_FRAGMENT_SIZE * 3 + 10)
+            .map(|byte| byte as u8)
+            .collect()
+    }
+
+    #[test]
+    fn reassembles_fragments_out_of_order() {
+        let mut reassembler = Reassembler::unreliable();
+        let now = Instant::now();
+        let mut messages = split(payload(), 7).unwrap();
+        assert_eq!(messages.len(), 4);
+
+        let last = messages.remove(1);
+
+        for message in messages.into_iter().rev() {
+            assert_eq!(reassembler.add(message, now), None);
+        }
+
+        assert_eq!(reassembler.add(last, now), Some(payload()));
+        assert!(reassembler.partials.is_empty());
+    }
+
+    #[test]
+    fn ignores_duplicate_fragments() {
+        let mut reassembler = Reassembler::unreliable();
+        let now = Instant::now();
+
+        assert_eq!(reassembler.add(fragment(1, 0, 2), now), None);
+        assert_eq!(reassembler.add(fragment(1, 0, 2), now), None);
+        assert_eq!(reassembler.add(fragment(1, 1, 2), now), Some(vec![0, 1]));
+    }
+
+    #[test]
+    fn drops_malformed_fragments() {
+        let mut reassembler = Reassembler::unreliable();
+        let now = Instant::now();
+        let oversized = Message::Fragment {
+            group: 1,
+            index: 0,
+            count: 2,
+            data: vec![0; MAX_FRAGMENT_SIZE + 1],
+        };
+
+        assert_eq!(reassembler.add(fragment(1, 2, 2), now), None);
+        assert_eq!(reassembler.add(fragment(1, 0, 0), now), None);
+        assert_eq!(
+            reassembler.add(fragment(1, 0, MAX_FRAGMENT_COUNT as u16 + 1), now),
+            None
+        );
+        assert_eq!(reassembler.add(oversized, now), None);
+        assert!(reassembler.partials.is_empty());
+    }
+
+    #[test]
+    fn drops_a_message_whose_fragment_count_changes() {
+        let mut reassembler = Reassembler::unreliable();
+        let now = Instant::now();
+
+        assert_eq!(reassembler.add(fragment(1, 0, 3), now), None);
+        assert_eq!(reassembler.add(fragment(1, 1, 4), now), None);
+        assert!(reassembler.partials.is_empty());
+    }
+
+    #[test]
+    fn expires_incomplete_unreliable_messages() {
+        let mut reassembler = Reassembler::unreliable();
+        let now = Instant::now();
+        reassembler.add(fragment(1, 0, 2), now);
+
+        reassembler.expire(now + REASSEMBLY_TIMEOUT / 2);
+        assert_eq!(reassembler.partials.len(), 1);
+
+        reassembler.expire(now + REASSEMBLY_TIMEOUT);
+        assert!(reassembler.partials.is_empty());
+    }
+
+    #[test]
+    fn evicts_the_oldest_unreliable_message() {
+        let mut reassembler = Reassembler::unreliable();
+        let now = Instant::now();
+
+        for group in 0..=MAX_PARTIAL_MESSAGES as FragmentGroup {
+            let started = now + Duration::from_millis(group as u64);
+            reassembler.add(fragment(group, 0, 2), started);
+        }
+
+        assert_eq!(reassembler.partials.len(), MAX_PARTIAL_MESSAGES);
+        assert!(!reassembler.partials.contains_key(&0));
+    }
+
+    #[test]
+    fn keeps_every_reliable_message() {
+        let mut reassembler = Reassembler::reliable();
+        let now = Instant::now();
+
+        for group in 0..=MAX_PARTIAL_MESSAGES as FragmentGroup {
+            let started = now + Duration::from_millis(group as u64);
+            reassembler.add(fragment(group, 0, 2), started);
+        }
+
+        reassembler.expire(now + REASSEMBLY_TIMEOUT * 2);
+        assert_eq!(reassembler.partials.len(), MAX_PARTIAL_MESSAGES + 1);
+        assert_eq!(reassembler.add(fragment(0, 1, 2), now), Some(vec![0, 1]));
+    }
+}
@@ -0,0 +1,88 @@
+use crate::game::entity::PlayerId;
+use crate::game::{BlockCoordinate, ChunkData, InputSequence, MovementInput};
+use crate::network::{EncodedSnapshot, QuantizedLook, QuantizedPosition, ServerTime};
+
+use core::events::{ClientEvent, ServerEvent};
+use math::vector::Vector3;
+use serde::{Deserialize, Serialize};
+
+/// Payload sent by the client, either a core event or something the core schema cannot express
+#[derive(Serialize, Deserialize)]
+pub enum ClientMessage {
+    Event(ClientEvent),
+    /// joins the game under a username, the core `PlayerConnect` event joins without one
+    Join {
+        username: String,
+    },
+    /// movement commands the server has not acknowledged yet, oldest first, along with where the
+    /// player looks and the latest snapshot received
+    PlayerUpdate {
+        inputs: Vec<MovementInput>,
+        look: QuantizedLook,
+        snapshot_ack: Option<ServerTime>,
+    },
+    /// latest snapshot received, sent on its own while the player does not move
+    SnapshotAck {
+        time: ServerTime,
+    },
+    /// asks to put `block` at `position`, air breaks the block that is there
+    EditBlock {
+        position: BlockCoordinate,
+        block: u8,
+    },
+    Chat {
+        text: String,
+    },
+}
+
+impl From<ClientEvent> for ClientMessage {
+    fn from(event: ClientEvent) -> Self {
+        ClientMessage::Event(event)
+    }
+}
+
+/// Payload sent by the server, either a core event or something the core schema cannot express
+#[derive(Serialize, Deserialize)]
+pub enum ServerMessage {
+    Event(ServerEvent),
+    /// authoritative position and motion of the main player once the input `sequence` was
+    /// applied
+    PositionAck {
+        sequence: InputSequence,
+        position: QuantizedPosition,
+        velocity: Vector3,
+        on_ground: bool,
+    },
+    /// state of every other player at a given server time, relative to the last snapshot the
+    /// client acknowledged
+    Snapshot {
+        time: ServerTime,
+        players: EncodedSnapshot,
+    },
+    /// blocks of a chunk close to the player, the world is not generated on the client
+    Chunk(ChunkData),
+    /// authoritative content of a block, sent after an edit or to undo a refused one
+    BlockUpdate {
+        position: BlockCoordinate,
+        block: u8,
+    },
+    /// chat message of a player, or of the server itself when there is no sender
+    Chat {
+        from: Option<PlayerId>,
+        text: String,
+    },
+    /// every player in the game, the client included
+    PlayerList(Vec<PlayerListEntry>),
+    /// what the server lets the player do, sent when it joins
+    Abilities {
+        flying: bool,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlayerListEntry {
+    pub id: PlayerId,
+    pub username: String,
+    /// round trip time between the server and the player in milliseconds
+    pub ping: u32,
+}
@@ -1,5 +1,35 @@
+mod bandwidth;
+mod channel;
+mod clock;
+mod connection_state;
+mod crypto;
+mod discovery;
+mod fragment;
+mod message;
 mod network_handler;
+mod packet;
+mod quantization;
+mod recording;
 mod remote_info;
+mod server_list;
+mod transport;
 
+pub use self::bandwidth::Bandwidth;
+pub use self::channel::{Channel, Delivery};
+pub use self::clock::{ServerClock, ServerTime};
+pub use self::connection_state::ConnectionState;
+pub use self::crypto::{
+    resume_proof, verify_resume_proof, KeyExchange, PublicKey, Role, SessionCipher,
+};
+pub use self::discovery::{Discovery, DiscoveryResponder, ServerDescription, DISCOVERY_PORT};
+pub use self::message::{ClientMessage, PlayerListEntry, ServerMessage};
 pub use self::network_handler::NetworkHandler;
-pub use self::remote_info::RemoteInfo;
+pub use self::packet::{Packet, RejectReason, Resume, Session, PROTOCOL_VERSION};
+pub use self::quantization::{
+    dequantize_angle, quantize_angle, EncodedSnapshot, QuantizedLook, QuantizedPosition,
+    QuantizedState, SnapshotHistory,
+};
+pub use self::recording::Replayer;
+pub use self::remote_info::{RemoteInfo, DEFAULT_PORT};
+pub use self::server_list::ServerList;
+pub use self::transport::{MemoryTransport, TcpTransport, Transport};
@@ -1,45 +1,527 @@
-use crate::network::RemoteInfo;
+use crate::network::channel::{Channel, Delivery};
+use crate::network::packet::{Packet, RejectReason, Resume, Session, PROTOCOL_VERSION};
+use crate::network::recording::Recorder;
+use crate::network::transport::{self, Transport};
+use crate::network::{
+    resume_proof, Bandwidth, ClientMessage, ConnectionState, KeyExchange, PublicKey, RemoteInfo,
+    Role, ServerClock, ServerMessage, SessionCipher,
+};
 
-use core::events::{ClientEvent, ServerEvent};
+use core::events::ClientEvent;
 use std::io;
-use std::net::UdpSocket;
+use std::time::{Duration, Instant};
 
-const MAX_MESSAGE_SIZE: usize = 65535;
+/// time between two handshake attempts
+const CONNECT_RETRY_INTERVAL: Duration = Duration::from_millis(500);
+/// time given to the server to answer the handshake
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+/// a heartbeat is sent when nothing else was sent for this long
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(1);
+/// the connection is considered lost when nothing was received for this long
+const CONNECTION_TIMEOUT: Duration = Duration::from_secs(10);
+/// delay before the first reconnection attempt, doubled after every failed attempt
+const RECONNECT_BASE_DELAY: Duration = Duration::from_millis(500);
+const RECONNECT_MAX_DELAY: Duration = Duration::from_secs(8);
+const MAX_RECONNECT_ATTEMPTS: u32 = 8;
+/// time between two time requests, shorter until the clock is synchronized
+const TIME_SYNC_INTERVAL: Duration = Duration::from_secs(1);
+const TIME_SYNC_FAST_INTERVAL: Duration = Duration::from_millis(100);
+/// server keys tried during a handshake, anyone can send one in clear
+const MAX_SERVER_KEYS: usize = 8;
 
 pub struct NetworkHandler {
-    socket: UdpSocket,
+    transport: Box<dyn Transport>,
+    /// where to open a new transport when reconnecting, none for in process transports
+    remote: Option<RemoteInfo>,
+    channel: Channel,
+    state: ConnectionState,
+    session: Option<Session>,
+    resumed: bool,
+    connect_start: Instant,
+    last_sent: Option<Instant>,
+    last_received: Instant,
+    recorder: Option<Recorder>,
+    clock: ServerClock,
+    last_time_request: Option<Instant>,
+    bandwidth: Bandwidth,
+    encrypted: bool,
+    /// key pair of the current handshake, kept while retrying it
+    key_exchange: Option<KeyExchange>,
+    /// keys agreed on with the server, set once a sealed packet proves which key is the server's
+    cipher: Option<SessionCipher>,
+    /// keys received in clear during the handshake, along with the cipher each one gives
+    server_keys: Vec<(PublicKey, SessionCipher)>,
+    /// refusal received in clear by an encrypted connection, it only becomes final when the
+    /// handshake times out since anyone could have sent it
+    rejection: Option<RejectReason>,
 }
 
 impl NetworkHandler {
     pub fn new(info: RemoteInfo) -> io::Result<Self> {
-        let socket = UdpSocket::bind("0.0.0.0:0")?;
-        socket.set_nonblocking(true).unwrap();
-        socket.connect(format!("{}:{}", info.ip, info.port))?;
+        let transport = transport::open(&info)?;
+        let mut handler = Self::with_transport(transport, info.encrypted);
 
-        Ok(Self { socket })
+        if let Some(path) = info.recording.as_ref() {
+            match Recorder::create(path) {
+                Ok(recorder) => handler.recorder = Some(recorder),
+                Err(err) => error!("<network> could not start recording: {}", err),
+            }
+        }
+
+        handler.remote = Some(info);
+
+        Ok(handler)
+    }
+
+    /// starts the handshake over an already opened transport, an encrypted connection agrees on
+    /// keys with the server before anything else is sent
+    pub fn with_transport(transport: Box<dyn Transport>, encrypted: bool) -> Self {
+        let mut handler = Self {
+            transport,
+            remote: None,
+            channel: Channel::new(),
+            state: ConnectionState::Connecting,
+            session: None,
+            resumed: false,
+            connect_start: Instant::now(),
+            last_sent: None,
+            last_received: Instant::now(),
+            recorder: None,
+            clock: ServerClock::new(),
+            last_time_request: None,
+            bandwidth: Bandwidth::new(),
+            encrypted,
+            key_exchange: None,
+            cipher: None,
+            server_keys: Vec::new(),
+            rejection: None,
+        };
+
+        if encrypted {
+            handler.key_exchange = Some(KeyExchange::new());
+        }
+
+        handler.update(Instant::now());
+        handler
+    }
+
+    pub fn state(&self) -> &ConnectionState {
+        &self.state
+    }
+
+    pub fn session(&self) -> Option<Session> {
+        self.session
+    }
+
+    /// indicates if the current connection picked up the session of a lost one
+    pub fn resumed(&self) -> bool {
+        self.resumed
+    }
+
+    /// server time and round trip time
+    pub fn clock(&self) -> &ServerClock {
+        &self.clock
+    }
+
+    /// bytes sent and received per second
+    pub fn bandwidth(&self) -> &Bandwidth {
+        &self.bandwidth
     }
 
-    pub fn send(&self, event: ClientEvent) {
-        let buffer = bincode::serialize(&event).unwrap();
+    pub fn send<M: Into<ClientMessage>>(&mut self, message: M) {
+        let message = message.into();
+
+        // movements are sent continuously, losing one is cheaper than waiting for it
+        let delivery = match message {
+            ClientMessage::Event(ClientEvent::PlayerMove { .. }) => Delivery::Unreliable,
+            ClientMessage::PlayerUpdate { .. } => Delivery::Unreliable,
+            ClientMessage::SnapshotAck { .. } => Delivery::Unreliable,
+            _ => Delivery::Reliable,
+        };
+
+        // reliable events wait for the handshake, there is no point in queuing anything else
+        let accepted = match (&self.state, delivery) {
+            (ConnectionState::Connected, _) => true,
+            (ConnectionState::Connecting, Delivery::Reliable) => true,
+            (ConnectionState::Reconnecting { .. }, Delivery::Reliable) => true,
+            _ => false,
+        };
+
+        if !accepted {
+            return;
+        }
+
+        match bincode::serialize(&message) {
+            Ok(payload) => {
+                self.record(|recorder| recorder.sent(&payload));
+                self.channel.send(payload, delivery);
+            }
+            Err(err) => error!("<network> could not serialize message: {}", err),
+        }
 
-        let _ = self.socket.send(&buffer);
+        if self.state == ConnectionState::Connected {
+            self.flush(Instant::now());
+        }
     }
 
-    pub fn process(&self) -> io::Result<Vec<ServerEvent>> {
-        let mut events = Vec::new();
+    pub fn process(&mut self) -> io::Result<Vec<ServerMessage>> {
+        let mut messages = Vec::new();
 
         loop {
-            let mut data = [0; MAX_MESSAGE_SIZE];
-            if self.socket.recv(&mut data).is_err() {
-                break;
+            let data = match self.transport.receive() {
+                Ok(Some(data)) => data,
+                Ok(None) => break,
+                Err(err) => {
+                    if self.state == ConnectionState::Connected {
+                        warn!("<network> transport failure: {}", err);
+                        self.reconnect(1, Instant::now());
+                    }
+
+                    break;
+                }
+            };
+
+            let packet: Packet = match bincode::deserialize(&data) {
+                Ok(packet) => packet,
+                Err(err) => {
+                    error!("<network> invalid packet: {}", err);
+                    continue;
+                }
+            };
+
+            let now = Instant::now();
+            self.bandwidth.received(data.len(), now);
+
+            // the packets in clear of an encrypted connection could come from anyone
+            let authentic = match packet {
+                Packet::Sealed { .. } => true,
+                _ => !self.encrypted,
+            };
+
+            let packet = match self.authenticate(packet) {
+                Some(packet) => packet,
+                None => continue,
+            };
+
+            if authentic {
+                self.last_received = now;
+            }
+
+            match packet {
+                Packet::ServerKey { key } => {
+                    if self.is_handshaking() && self.cipher.is_none() {
+                        self.add_server_key(&key);
+                    }
+                }
+                Packet::Accept {
+                    protocol_version,
+                    session,
+                } => {
+                    if self.is_handshaking() {
+                        let resumed = self.session == Some(session);
+                        self.resumed = resumed;
+                        self.session = Some(session);
+                        self.state = ConnectionState::Connected;
+                        self.last_time_request = None;
+                        self.record(|recorder| recorder.connected(session, resumed));
+
+                        // a new session might be with a restarted server
+                        if !resumed {
+                            self.clock = ServerClock::new();
+                        }
+
+                        info!(
+                            "<network> connected to server (protocol version {}, {} session)",
+                            protocol_version,
+                            if self.resumed { "resumed" } else { "new" }
+                        );
+                    }
+                }
+                Packet::Reject { reason } => {
+                    if self.is_handshaking() && !authentic {
+                        warn!("<network> unauthenticated refusal: {}", reason);
+                        self.rejection = Some(reason);
+                    } else if self.is_handshaking() {
+                        error!("<network> connection refused: {}", reason);
+                        self.state = ConnectionState::Rejected(reason);
+                    }
+                }
+                Packet::TimeResponse {
+                    client_time,
+                    receive_time,
+                    send_time,
+                } => {
+                    if self.state == ConnectionState::Connected {
+                        self.clock.synchronize(client_time, receive_time, send_time);
+                    }
+                }
+                Packet::Connect { .. }
+                | Packet::Heartbeat
+                | Packet::TimeRequest { .. }
+                | Packet::Sealed { .. } => {}
+                packet => {
+                    if self.state != ConnectionState::Connected {
+                        continue;
+                    }
+
+                    for payload in self.channel.receive(packet, now) {
+                        self.record(|recorder| recorder.received(&payload));
+
+                        match bincode::deserialize(&payload) {
+                            Ok(message) => messages.push(message),
+                            Err(err) => error!("{}", err),
+                        }
+                    }
+                }
+            }
+        }
+
+        self.update(Instant::now());
+        self.bandwidth.update(Instant::now());
+        self.record(|recorder| recorder.flush());
+
+        Ok(messages)
+    }
+
+    /// drives the handshake, the heartbeats, the timeouts and the reconnection attempts
+    fn update(&mut self, now: Instant) {
+        match self.state {
+            ConnectionState::Connecting => {
+                if now.duration_since(self.connect_start) >= CONNECT_TIMEOUT {
+                    self.state = match self.rejection.take() {
+                        Some(reason) => {
+                            error!("<network> connection refused: {}", reason);
+                            ConnectionState::Rejected(reason)
+                        }
+                        None => {
+                            error!("<network> the server did not answer the handshake");
+                            ConnectionState::TimedOut
+                        }
+                    };
+                } else {
+                    self.handshake(now);
+                }
+            }
+            ConnectionState::Connected => {
+                if now.duration_since(self.last_received) >= CONNECTION_TIMEOUT {
+                    warn!("<network> lost connection to the server");
+                    self.reconnect(1, now);
+                    return;
+                }
+
+                // acks and retransmissions
+                self.flush(now);
+                self.request_time(now);
+
+                if self.elapsed_since_sent(now) >= HEARTBEAT_INTERVAL {
+                    self.send_packet(&Packet::Heartbeat, now);
+                }
             }
+            ConnectionState::Reconnecting { attempt } => {
+                if now < self.connect_start {
+                    // backing off
+                } else if now.duration_since(self.connect_start) >= CONNECT_TIMEOUT {
+                    if let Some(reason) = self.rejection.take() {
+                        error!("<network> reconnection refused: {}", reason);
+                        self.state = ConnectionState::Rejected(reason);
+                    } else if attempt >= MAX_RECONNECT_ATTEMPTS {
+                        error!(
+                            "<network> could not reconnect after {} attempts",
+                            MAX_RECONNECT_ATTEMPTS
+                        );
+                        self.state = ConnectionState::TimedOut;
+                    } else {
+                        self.reconnect(attempt + 1, now);
+                    }
+                } else {
+                    self.handshake(now);
+                }
+            }
+            ConnectionState::TimedOut | ConnectionState::Rejected(_) => {}
+        }
+    }
+
+    fn is_handshaking(&self) -> bool {
+        match self.state {
+            ConnectionState::Connecting | ConnectionState::Reconnecting { .. } => true,
+            _ => false,
+        }
+    }
+
+    fn handshake(&mut self, now: Instant) {
+        if self.elapsed_since_sent(now) >= CONNECT_RETRY_INTERVAL {
+            let key = self
+                .key_exchange
+                .as_ref()
+                .map(|exchange| exchange.public_key());
+
+            // the token of an encrypted session never goes in clear
+            let resume = self.session.map(|session| match key {
+                Some(key) => Resume::Proof {
+                    player_id: session.player_id,
+                    proof: resume_proof(session.token, &key),
+                },
+                None => Resume::Token(session),
+            });
+
+            self.send_packet(
+                &Packet::Connect {
+                    protocol_version: PROTOCOL_VERSION,
+                    resume,
+                    key,
+                },
+                now,
+            );
+        }
+    }
+
+    /// keeps the key as a candidate, the server's is the one its sealed accept opens with
+    fn add_server_key(&mut self, server_key: &PublicKey) {
+        let known = self.server_keys.iter().any(|(key, _)| key == server_key);
+
+        if known || self.server_keys.len() >= MAX_SERVER_KEYS {
+            return;
+        }
+
+        let cipher = self
+            .key_exchange
+            .as_ref()
+            .and_then(|exchange| exchange.complete(server_key, Role::Client));
+
+        match cipher {
+            Some(cipher) => self.server_keys.push((*server_key, cipher)),
+            None => warn!("<network> ignored an invalid server key"),
+        }
+    }
+
+    /// the packet once it is known to come from the server, none when it has to be dropped.
+    /// An encrypted connection only takes the key exchange and the refusals in clear.
+    fn authenticate(&mut self, packet: Packet) -> Option<Packet> {
+        match packet {
+            Packet::Sealed { counter, data } => {
+                if let Some(cipher) = self.cipher.as_mut() {
+                    return cipher.open(counter, &data);
+                }
+
+                // the first packet that opens tells which of the keys came from the server
+                let (index, packet) = self
+                    .server_keys
+                    .iter_mut()
+                    .enumerate()
+                    .find_map(|(index, (_, cipher))| Some((index, cipher.open(counter, &data)?)))?;
+                let (_, cipher) = self.server_keys.swap_remove(index);
+                self.server_keys.clear();
+                self.cipher = Some(cipher);
+
+                Some(packet)
+            }
+            Packet::ServerKey { .. } | Packet::Reject { .. } => Some(packet),
+            _ if self.encrypted => None,
+            packet => Some(packet),
+        }
+    }
 
-            match bincode::deserialize(&data) {
-                Ok(event) => events.push(event),
-                Err(err) => error!("{}", err),
+    fn request_time(&mut self, now: Instant) {
+        let interval = if self.clock.is_synchronized() {
+            TIME_SYNC_INTERVAL
+        } else {
+            TIME_SYNC_FAST_INTERVAL
+        };
+
+        let due = self
+            .last_time_request
+            .map_or(true, |last| now.duration_since(last) >= interval);
+
+        if due {
+            let client_time = self.clock.local_time();
+            self.send_packet(&Packet::TimeRequest { client_time }, now);
+            self.last_time_request = Some(now);
+        }
+    }
+
+    /// schedules a new handshake after an exponential back off, the server starts a fresh
+    /// channel for the resumed session so ours is reset as well
+    fn reconnect(&mut self, attempt: u32, now: Instant) {
+        let delay = (RECONNECT_BASE_DELAY * 2u32.pow(attempt.min(16) - 1)).min(RECONNECT_MAX_DELAY);
+
+        info!(
+            "<network> reconnection attempt {} in {}ms",
+            attempt,
+            delay.as_millis()
+        );
+
+        self.state = ConnectionState::Reconnecting { attempt };
+        self.channel = Channel::new();
+        self.connect_start = now + delay;
+        self.last_sent = None;
+
+        // a new handshake agrees on new keys
+        self.cipher = None;
+        self.server_keys.clear();
+        self.rejection = None;
+
+        if self.encrypted {
+            self.key_exchange = Some(KeyExchange::new());
+        }
+
+        // a stream transport does not survive the loss of its connection
+        if let Some(info) = self.remote.as_ref() {
+            match transport::open(info) {
+                Ok(transport) => self.transport = transport,
+                Err(err) => warn!(
+                    "<network> could not reopen the {} transport: {}",
+                    info.transport, err
+                ),
+            }
+        }
+    }
+
+    /// writes to the recording, which stops at the first failure
+    fn record<F: FnOnce(&mut Recorder) -> io::Result<()>>(&mut self, write: F) {
+        if let Some(recorder) = self.recorder.as_mut() {
+            if let Err(err) = write(recorder) {
+                error!("<network> recording stopped: {}", err);
+                self.recorder = None;
             }
         }
+    }
+
+    fn elapsed_since_sent(&self, now: Instant) -> Duration {
+        self.last_sent
+            .map_or(Duration::from_secs(u64::MAX), |last_sent| {
+                now.duration_since(last_sent)
+            })
+    }
+
+    fn flush(&mut self, now: Instant) {
+        for packet in self.channel.poll(now) {
+            self.send_packet(&packet, now);
+        }
+    }
 
-        Ok(events)
+    /// seals everything but the handshake of an encrypted connection, nothing else is sent
+    /// before the keys are agreed on
+    fn send_packet(&mut self, packet: &Packet, now: Instant) {
+        let sealed = match (packet, self.cipher.as_mut()) {
+            (Packet::Connect { .. }, _) => None,
+            (packet, Some(cipher)) => match cipher.seal(packet) {
+                Some(sealed) => Some(sealed),
+                None => {
+                    error!("<network> could not seal packet");
+                    return;
+                }
+            },
+            (_, None) if self.encrypted => return,
+            (_, None) => None,
+        };
+
+        match bincode::serialize(sealed.as_ref().unwrap_or(packet)) {
+            Ok(buffer) => {
+                let _ = self.transport.send(&buffer);
+                self.last_sent = Some(now);
+                self.bandwidth.sent(buffer.len(), now);
+            }
+            Err(err) => error!("<network> could not serialize packet: {}", err),
+        }
     }
 }
@@ -0,0 +1,124 @@
+use crate::game::entity::PlayerId;
+use crate::network::crypto::{PublicKey, ResumeProof};
+use crate::network::fragment::Message;
+use crate::network::ServerTime;
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+pub type Sequence = u16;
+
+/// Version of the wire protocol, including the `core` event schema. Bump it whenever one of the
+/// packets or events changes shape, peers with a different version refuse to talk to each other.
+pub const PROTOCOL_VERSION: u32 = 14;
+
+/// Datagram exchanged between the client and the server
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Packet {
+    /// sent by the client until the server answers with `Accept` or `Reject`. A client that lost
+    /// its connection asks to resume its previous session. Offering a key asks for an encrypted
+    /// session.
+    Connect {
+        protocol_version: u32,
+        resume: Option<Resume>,
+        key: Option<PublicKey>,
+    },
+    /// answers a `Connect` that offered a key, the `Accept` follows sealed
+    ServerKey {
+        key: PublicKey,
+    },
+    Accept {
+        protocol_version: u32,
+        session: Session,
+    },
+    Reject {
+        reason: RejectReason,
+    },
+    /// keeps the connection alive when there is nothing else to send
+    Heartbeat,
+    /// message that is retransmitted until acknowledged and delivered in order
+    Reliable {
+        sequence: Sequence,
+        message: Message,
+    },
+    /// fire and forget message, stale ones are dropped on arrival. Fragments of the same message
+    /// share their sequence.
+    Unreliable {
+        sequence: Sequence,
+        message: Message,
+    },
+    /// acknowledges reliable messages
+    Ack {
+        sequences: Vec<Sequence>,
+    },
+    /// asks for the server time, `client_time` is the local time of the client when sending it
+    TimeRequest {
+        client_time: u64,
+    },
+    /// answers a time request right away with the server time at which it was received and at
+    /// which the response was sent
+    TimeResponse {
+        client_time: u64,
+        receive_time: ServerTime,
+        send_time: ServerTime,
+    },
+    /// packet encrypted with the keys of the session, every packet of an encrypted session is
+    /// sent this way once the keys are agreed on
+    Sealed {
+        counter: u64,
+        data: Vec<u8>,
+    },
+}
+
+/// Identifies a player across connections, the token proves the session belongs to the client
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Session {
+    pub player_id: PlayerId,
+    pub token: u64,
+}
+
+/// Asks to pick up the session of a lost connection
+#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
+pub enum Resume {
+    /// a plain session hands its token over
+    Token(Session),
+    /// an encrypted session never sends its token in clear, it proves it knows it instead
+    Proof {
+        player_id: PlayerId,
+        proof: ResumeProof,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum RejectReason {
+    VersionMismatch {
+        server: u32,
+    },
+    ServerFull,
+    /// the session was encrypted, it can only be resumed by an encrypted connection
+    EncryptionRequired,
+    /// the peers could not agree on the keys of an encrypted session
+    HandshakeFailed,
+}
+
+impl fmt::Display for RejectReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RejectReason::VersionMismatch { server } => write!(
+                f,
+                "server runs protocol version {}, client runs version {}",
+                server, PROTOCOL_VERSION
+            ),
+            RejectReason::ServerFull => write!(f, "server is full"),
+            RejectReason::EncryptionRequired => write!(f, "the session requires encryption"),
+            RejectReason::HandshakeFailed => write!(f, "could not agree on the session keys"),
+        }
+    }
+}
+
+/// indicates if the sequence `a` comes after `b`, taking wrap around into account
+pub fn sequence_greater_than(a: Sequence, b: Sequence) -> bool {
+    const HALF: Sequence = Sequence::MAX / 2 + 1;
+
+    ((a > b) && (a - b <= HALF)) || ((a < b) && (b - a > HALF))
+}
@@ -0,0 +1,375 @@
+use crate::game::entity::{PlayerId, PlayerState};
+use crate::game::MovementFlags;
+use crate::network::ServerTime;
+
+use core::world::WorldCoordinate;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+
+/// steps per block, positions are sent with a 1/256th of a block precision
+const POSITION_SCALE: f32 = 256.0;
+/// steps per full turn
+const ANGLE_SCALE: f32 = 65536.0 / 360.0;
+/// snapshots kept to decode the ones encoded against them, a bit more than a second
+const MAX_SNAPSHOT_HISTORY: usize = 32;
+
+/// angle in degrees quantized to a 16 bits fraction of a turn
+pub fn quantize_angle(angle: f32) -> u16 {
+    (angle.rem_euclid(360.0) * ANGLE_SCALE).round() as u32 as u16
+}
+
+/// degrees between -180 and 180
+pub fn dequantize_angle(angle: u16) -> f32 {
+    angle as i16 as f32 / ANGLE_SCALE
+}
+
+/// Position in fixed point
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub struct QuantizedPosition {
+    pub x: i32,
+    pub y: i32,
+    pub z: i32,
+}
+
+impl QuantizedPosition {
+    pub fn new(position: WorldCoordinate) -> Self {
+        Self {
+            x: (position.x * POSITION_SCALE).round() as i32,
+            y: (position.y * POSITION_SCALE).round() as i32,
+            z: (position.z * POSITION_SCALE).round() as i32,
+        }
+    }
+
+    pub fn position(&self) -> WorldCoordinate {
+        WorldCoordinate {
+            x: self.x as f32 / POSITION_SCALE,
+            y: self.y as f32 / POSITION_SCALE,
+            z: self.z as f32 / POSITION_SCALE,
+        }
+    }
+
+    /// difference with `base`, none when it does not fit, which takes moving more than 128
+    /// blocks at once
+    fn delta(&self, base: &QuantizedPosition) -> Option<PositionDelta> {
+        Some(PositionDelta {
+            x: i16_delta(self.x, base.x)?,
+            y: i16_delta(self.y, base.y)?,
+            z: i16_delta(self.z, base.z)?,
+        })
+    }
+
+    fn apply(&self, delta: PositionDelta) -> QuantizedPosition {
+        QuantizedPosition {
+            x: self.x.wrapping_add(delta.x as i32),
+            y: self.y.wrapping_add(delta.y as i32),
+            z: self.z.wrapping_add(delta.z as i32),
+        }
+    }
+}
+
+fn i16_delta(value: i32, base: i32) -> Option<i16> {
+    let delta = value.checked_sub(base)?;
+
+    if delta >= i16::MIN as i32 && delta <= i16::MAX as i32 {
+        Some(delta as i16)
+    } else {
+        None
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PositionDelta {
+    x: i16,
+    y: i16,
+    z: i16,
+}
+
+/// Where a player looks and what it is doing
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub struct QuantizedLook {
+    pub yaw: u16,
+    pub pitch: u16,
+    pub flags: MovementFlags,
+}
+
+impl QuantizedLook {
+    pub fn new(state: &PlayerState) -> Self {
+        Self {
+            yaw: quantize_angle(state.yaw),
+            pitch: quantize_angle(state.pitch),
+            flags: state.flags,
+        }
+    }
+}
+
+/// `PlayerState` the way it is sent
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub struct QuantizedState {
+    pub position: QuantizedPosition,
+    pub look: QuantizedLook,
+}
+
+impl QuantizedState {
+    pub fn new(state: &PlayerState) -> Self {
+        Self {
+            position: QuantizedPosition::new(state.position),
+            look: QuantizedLook::new(state),
+        }
+    }
+
+    pub fn state(&self) -> PlayerState {
+        PlayerState {
+            position: self.position.position(),
+            yaw: dequantize_angle(self.look.yaw),
+            pitch: dequantize_angle(self.look.pitch),
+            flags: self.look.flags,
+        }
+    }
+}
+
+/// State of a player in a snapshot, relative to the same player in the base snapshot when
+/// possible. Players that did not change since the base are left out.
+#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
+pub enum StateUpdate {
+    Full(QuantizedState),
+    Delta {
+        position: PositionDelta,
+        look: QuantizedLook,
+    },
+}
+
+/// quantized state of every player of a snapshot
+pub type SnapshotStates = HashMap<PlayerId, QuantizedState>;
+
+/// Players of a snapshot encoded against a base snapshot the receiver acknowledged
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EncodedSnapshot {
+    /// time of the base snapshot, none when every player is sent in full
+    pub base: Option<ServerTime>,
+    pub updates: Vec<(PlayerId, StateUpdate)>,
+    /// players of the base that are not part of the snapshot anymore
+    pub removed: Vec<PlayerId>,
+}
+
+impl EncodedSnapshot {
+    pub fn encode(base: Option<(ServerTime, &SnapshotStates)>, states: &SnapshotStates) -> Self {
+        let base_states = base.map(|(_, states)| states);
+
+        let updates = states
+            .iter()
+            .filter_map(|(id, state)| {
+                let previous = base_states.and_then(|base_states| base_states.get(id));
+
+                if previous == Some(state) {
+                    return None;
+                }
+
+                let update = previous
+                    .and_then(|previous| state.position.delta(&previous.position))
+                    .map_or(StateUpdate::Full(*state), |position| StateUpdate::Delta {
+                        position,
+                        look: state.look,
+                    });
+
+                Some((*id, update))
+            })
+            .collect();
+
+        let removed = base_states.map_or_else(Vec::new, |base_states| {
+            base_states
+                .keys()
+                .filter(|id| !states.contains_key(id))
+                .copied()
+                .collect()
+        });
+
+        Self {
+            base: base.map(|(time, _)| time),
+            updates,
+            removed,
+        }
+    }
+
+    /// nothing changed since the base
+    pub fn is_empty(&self) -> bool {
+        self.base.is_some() && self.updates.is_empty() && self.removed.is_empty()
+    }
+
+    /// none when a delta refers to a player the base does not have
+    pub fn decode(&self, base_states: Option<&SnapshotStates>) -> Option<SnapshotStates> {
+        let mut states = base_states.cloned().unwrap_or_default();
+
+        for id in self.removed.iter() {
+            states.remove(id);
+        }
+
+        for (id, update) in self.updates.iter() {
+            let state = match update {
+                StateUpdate::Full(state) => *state,
+                StateUpdate::Delta { position, look } => QuantizedState {
+                    position: states.get(id)?.position.apply(*position),
+                    look: *look,
+                },
+            };
+
+            states.insert(*id, state);
+        }
+
+        Some(states)
+    }
+}
+
+/// Latest snapshots, to encode or decode the next ones against them
+pub struct SnapshotHistory {
+    snapshots: VecDeque<(ServerTime, SnapshotStates)>,
+}
+
+impl SnapshotHistory {
+    pub fn new() -> Self {
+        Self {
+            snapshots: VecDeque::new(),
+        }
+    }
+
+    pub fn insert(&mut self, time: ServerTime, states: SnapshotStates) {
+        self.snapshots.push_back((time, states));
+
+        if self.snapshots.len() > MAX_SNAPSHOT_HISTORY {
+            self.snapshots.pop_front();
+        }
+    }
+
+    pub fn get(&self, time: ServerTime) -> Option<&SnapshotStates> {
+        self.snapshots
+            .iter()
+            .find(|(t, _)| *t == time)
+            .map(|(_, states)| states)
+    }
+
+    /// time of the latest snapshot
+    pub fn latest(&self) -> Option<ServerTime> {
+        self.snapshots.back().map(|(time, _)| *time)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state(x: i32) -> QuantizedState {
+        QuantizedState {
+            position: QuantizedPosition { x, y: 0, z: 0 },
+            look: QuantizedLook {
+                yaw: 0,
+                pitch: 0,
+                flags: MovementFlags::default(),
+            },
+        }
+    }
+
+    fn states(players: &[(PlayerId, i32)]) -> SnapshotStates {
+        players.iter().map(|(id, x)| (*id, state(*x))).collect()
+    }
+
+    fn update(snapshot: &EncodedSnapshot, id: PlayerId) -> Option<StateUpdate> {
+        snapshot
+            .updates
+            .iter()
+            .find(|(other, _)| *other == id)
+            .map(|(_, update)| *update)
+    }
+
+    #[test]
+    fn sends_everything_in_full_without_a_base() {
+        let current = states(&[(1, 0), (2, 100)]);
+        let snapshot = EncodedSnapshot::encode(None, &current);
+
+        assert_eq!(snapshot.base, None);
+        assert_eq!(snapshot.updates.len(), 2);
+        assert!(matches!(update(&snapshot, 1), Some(StateUpdate::Full(_))));
+        assert_eq!(snapshot.decode(None), Some(current));
+    }
+
+    #[test]
+    fn encodes_changes_against_the_base() {
+        let base = states(&[(1, 0), (2, 100), (3, 200)]);
+        let current = states(&[(1, 0), (2, 110), (4, 300)]);
+        let snapshot = EncodedSnapshot::encode(Some((10, &base)), &current);
+
+        assert_eq!(snapshot.base, Some(10));
+        assert!(update(&snapshot, 1).is_none());
+        assert!(matches!(
+            update(&snapshot, 2),
+            Some(StateUpdate::Delta { .. })
+        ));
+        assert!(matches!(update(&snapshot, 4), Some(StateUpdate::Full(_))));
+        assert_eq!(snapshot.removed, vec![3]);
+        assert_eq!(snapshot.decode(Some(&base)), Some(current));
+    }
+
+    #[test]
+    fn nothing_changed_since_the_base() {
+        let base = states(&[(1, 0)]);
+        let snapshot = EncodedSnapshot::encode(Some((10, &base)), &base);
+
+        assert!(snapshot.is_empty());
+        assert_eq!(snapshot.decode(Some(&base)), Some(base));
+    }
+
+    #[test]
+    fn refuses_a_delta_against_a_missing_player() {
+        let snapshot = EncodedSnapshot {
+            base: Some(10),
+            updates: vec![(
+                1,
+                StateUpdate::Delta {
+                    position: PositionDelta { x: 1, y: 0, z: 0 },
+                    look: state(0).look,
+                },
+            )],
+            removed: Vec::new(),
+        };
+
+        assert_eq!(snapshot.decode(Some(&states(&[(2, 0)]))), None);
+    }
+
+    #[test]
+    fn sends_long_moves_in_full() {
+        let far = i16::MAX as i32 + 1;
+        let base = states(&[(1, 0)]);
+        let current = states(&[(1, far)]);
+        let snapshot = EncodedSnapshot::encode(Some((10, &base)), &current);
+
+        assert!(matches!(update(&snapshot, 1), Some(StateUpdate::Full(_))));
+        assert_eq!(snapshot.decode(Some(&base)), Some(current));
+    }
+
+    #[test]
+    fn quantizes_angles() {
+        assert_eq!(quantize_angle(0.0), 0);
+        assert_eq!(dequantize_angle(0), 0.0);
+        assert_eq!(quantize_angle(180.0), 32768);
+        assert_eq!(quantize_angle(-180.0), 32768);
+        assert_eq!(dequantize_angle(32768), -180.0);
+        assert_eq!(quantize_angle(90.0), 16384);
+        assert_eq!(dequantize_angle(quantize_angle(-90.0)), -90.0);
+
+        // just below a full turn is just below zero, rounding up wraps to zero
+        assert_eq!(quantize_angle(359.99), 65534);
+        assert!((dequantize_angle(65534) + 0.01).abs() < 0.005);
+        assert_eq!(quantize_angle(359.999), 0);
+    }
+
+    #[test]
+    fn forgets_the_oldest_snapshots() {
+        let mut history = SnapshotHistory::new();
+
+        for time in 0..=MAX_SNAPSHOT_HISTORY as ServerTime {
+            history.insert(time, states(&[(1, time as i32)]));
+        }
+
+        assert!(history.get(0).is_none());
+        assert_eq!(history.get(1), Some(&states(&[(1, 1)])));
+        assert_eq!(history.latest(), Some(MAX_SNAPSHOT_HISTORY as ServerTime));
+    }
+}
@@ -0,0 +1,336 @@
+use crate::network::packet::{Session, PROTOCOL_VERSION};
+use crate::network::{ServerMessage, ServerTime};
+
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+/// environment variable holding the path of the file to record the session to
+pub const RECORDING_VAR: &'static str = "ROADRUNNER_RECORD";
+const MAGIC: [u8; 4] = *b"RRRC";
+
+#[derive(Serialize, Deserialize)]
+struct Header {
+    magic: [u8; 4],
+    protocol_version: u32,
+}
+
+/// Payloads are stored exactly as they travel, encoded with bincode
+#[derive(Serialize, Deserialize)]
+enum Record {
+    Connected { session: Session, resumed: bool },
+    Received(Vec<u8>),
+    Sent(Vec<u8>),
+}
+
+#[derive(Serialize, Deserialize)]
+struct Entry {
+    /// milliseconds since the start of the recording
+    time: u64,
+    record: Record,
+}
+
+/// Writes the messages exchanged with the server to a file
+pub struct Recorder {
+    writer: BufWriter<File>,
+    start: Instant,
+}
+
+impl Recorder {
+    pub fn create<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let mut writer = BufWriter::new(File::create(path.as_ref())?);
+
+        bincode::serialize_into(
+            &mut writer,
+            &Header {
+                magic: MAGIC,
+                protocol_version: PROTOCOL_VERSION,
+            },
+        )
+        .map_err(to_io_error)?;
+
+        info!("<recorder> recording to {}", path.as_ref().display());
+
+        Ok(Self {
+            writer,
+            start: Instant::now(),
+        })
+    }
+
+    /// path given through `RECORDING_VAR`, none when it is not set
+    pub fn path_from_env() -> Option<PathBuf> {
+        env::var_os(RECORDING_VAR).map(PathBuf::from)
+    }
+
+    pub fn connected(&mut self, session: Session, resumed: bool) -> io::Result<()> {
+        self.write(Record::Connected { session, resumed })
+    }
+
+    pub fn received(&mut self, payload: &[u8]) -> io::Result<()> {
+        self.write(Record::Received(payload.to_vec()))
+    }
+
+    pub fn sent(&mut self, payload: &[u8]) -> io::Result<()> {
+        self.write(Record::Sent(payload.to_vec()))
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+
+    fn write(&mut self, record: Record) -> io::Result<()> {
+        let entry = Entry {
+            time: self.start.elapsed().as_millis() as u64,
+            record,
+        };
+
+        bincode::serialize_into(&mut self.writer, &entry).map_err(to_io_error)
+    }
+}
+
+/// Plays a recording back, messages are handed out as the replay time reaches their timestamp
+pub struct Replayer {
+    entries: Vec<Entry>,
+    next: usize,
+    /// milliseconds since the start of the replay
+    time: u64,
+    session: Option<Session>,
+    resumed: bool,
+    /// server time minus replay time, given by the first snapshot of the session. The server
+    /// time follows the replay time so the players are placed the same way on every run.
+    offset: Option<i64>,
+}
+
+impl Replayer {
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let mut reader = BufReader::new(File::open(path.as_ref())?);
+
+        let header: Header = bincode::deserialize_from(&mut reader).map_err(to_io_error)?;
+
+        if header.magic != MAGIC {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "not a recording",
+            ));
+        }
+
+        if header.protocol_version != PROTOCOL_VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "recorded with protocol version {}, client runs version {}",
+                    header.protocol_version, PROTOCOL_VERSION
+                ),
+            ));
+        }
+
+        let mut entries = Vec::new();
+
+        loop {
+            match bincode::deserialize_from(&mut reader) {
+                Ok(entry) => entries.push(entry),
+                Err(err) => match *err {
+                    bincode::ErrorKind::Io(ref err)
+                        if err.kind() == io::ErrorKind::UnexpectedEof =>
+                    {
+                        break
+                    }
+                    _ => return Err(to_io_error(err)),
+                },
+            }
+        }
+
+        info!(
+            "<replayer> replaying {} entries from {}",
+            entries.len(),
+            path.as_ref().display()
+        );
+
+        Ok(Self::new(entries))
+    }
+
+    fn new(entries: Vec<Entry>) -> Self {
+        Self {
+            entries,
+            next: 0,
+            time: 0,
+            session: None,
+            resumed: false,
+            offset: None,
+        }
+    }
+
+    pub fn session(&self) -> Option<Session> {
+        self.session
+    }
+
+    pub fn resumed(&self) -> bool {
+        self.resumed
+    }
+
+    /// server time at the current replay time, none until the first snapshot
+    pub fn server_time(&self) -> Option<ServerTime> {
+        self.offset
+            .map(|offset| (self.time as i64 + offset).max(0) as ServerTime)
+    }
+
+    /// moves the replay forward by `time_delta` milliseconds and returns the messages received
+    /// in that time, in their original order
+    pub fn advance(&mut self, time_delta: u64) -> Vec<ServerMessage> {
+        let mut messages = Vec::new();
+        self.time += time_delta;
+
+        if self.next >= self.entries.len() {
+            return messages;
+        }
+
+        while let Some(entry) = self.entries.get(self.next) {
+            if entry.time > self.time {
+                break;
+            }
+
+            self.next += 1;
+
+            match entry.record {
+                Record::Connected { session, resumed } => {
+                    self.session = Some(session);
+                    self.resumed = resumed;
+
+                    // a new session might be with a restarted server
+                    if !resumed {
+                        self.offset = None;
+                    }
+                }
+                Record::Received(ref payload) => match bincode::deserialize(payload) {
+                    Ok(message) => {
+                        if let ServerMessage::Snapshot { time, .. } = &message {
+                            if self.offset.is_none() {
+                                self.offset = Some(*time as i64 - entry.time as i64);
+                            }
+                        }
+
+                        messages.push(message);
+                    }
+                    Err(err) => error!("<replayer> invalid message: {}", err),
+                },
+                // the player drives the client, what it sent is only kept for inspection
+                Record::Sent(_) => {}
+            }
+        }
+
+        if self.next >= self.entries.len() {
+            info!("<replayer> end of the recording");
+        }
+
+        messages
+    }
+}
+
+fn to_io_error(err: bincode::Error) -> io::Error {
+    match *err {
+        bincode::ErrorKind::Io(err) => err,
+        err => io::Error::new(io::ErrorKind::InvalidData, err.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::EncodedSnapshot;
+
+    fn received(time: u64, message: &ServerMessage) -> Entry {
+        Entry {
+            time,
+            record: Record::Received(bincode::serialize(message).unwrap()),
+        }
+    }
+
+    fn snapshot(time: ServerTime) -> ServerMessage {
+        ServerMessage::Snapshot {
+            time,
+            players: EncodedSnapshot {
+                base: None,
+                updates: Vec::new(),
+                removed: Vec::new(),
+            },
+        }
+    }
+
+    fn chat(text: &str) -> ServerMessage {
+        ServerMessage::Chat {
+            from: None,
+            text: String::from(text),
+        }
+    }
+
+    fn recording() -> Vec<Entry> {
+        vec![
+            Entry {
+                time: 0,
+                record: Record::Connected {
+                    session: Session {
+                        player_id: 1,
+                        token: 2,
+                    },
+                    resumed: false,
+                },
+            },
+            received(30, &snapshot(5000)),
+            received(55, &chat("a")),
+            received(130, &snapshot(5100)),
+            received(170, &chat("b")),
+        ]
+    }
+
+    /// replay time after each step, with the messages it gave and the server time
+    fn play(steps: &[u64]) -> Vec<(u64, Vec<Vec<u8>>, Option<ServerTime>)> {
+        let mut replayer = Replayer::new(recording());
+
+        steps
+            .iter()
+            .map(|step| {
+                let messages = replayer
+                    .advance(*step)
+                    .iter()
+                    .map(|message| bincode::serialize(message).unwrap())
+                    .collect();
+
+                (replayer.time, messages, replayer.server_time())
+            })
+            .collect()
+    }
+
+    /// every message given up to `time` and the server time then
+    fn at(
+        played: &[(u64, Vec<Vec<u8>>, Option<ServerTime>)],
+        time: u64,
+    ) -> (Vec<Vec<u8>>, Option<ServerTime>) {
+        let steps = played.iter().take_while(|(t, _, _)| *t <= time);
+        let messages = steps.clone().flat_map(|(_, m, _)| m.clone()).collect();
+        let server_time = steps.last().and_then(|(_, _, server_time)| *server_time);
+
+        (messages, server_time)
+    }
+
+    #[test]
+    fn plays_the_same_whatever_the_frame_split() {
+        let short = play(&[20; 10]);
+        let long = play(&[40; 5]);
+        let longest = play(&[100, 100]);
+
+        for time in (40..=200).step_by(40) {
+            assert_eq!(at(&short, time), at(&long, time));
+        }
+
+        assert_eq!(at(&short, 100), at(&longest, 100));
+        assert_eq!(at(&short, 200), at(&longest, 200));
+
+        let (messages, server_time) = at(&short, 200);
+        assert_eq!(messages.len(), 4);
+        assert_eq!(server_time, Some(200 + 5000 - 30));
+        assert_eq!(at(&short, 20).1, None);
+    }
+}
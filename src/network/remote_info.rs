@@ -1,14 +1,271 @@
-// TODO: remove this dead_code attribute when we have a menu for this
-#[allow(dead_code)]
+use crate::network::recording::Recorder;
+use crate::network::transport::{NetworkConditions, TransportKind};
+
+use std::error::Error;
+use std::fmt;
+use std::net::Ipv6Addr;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+/// port used when an address does not specify one
+pub const DEFAULT_PORT: u16 = 25565;
+const MAX_HOSTNAME_LENGTH: usize = 253;
+const MAX_LABEL_LENGTH: usize = 63;
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct RemoteInfo {
-    pub ip: String,
+    /// hostname, ipv4 or ipv6 address, without brackets
+    pub host: String,
     pub port: u16,
+    pub transport: TransportKind,
+    /// degrades the traffic to reproduce bad networks
+    pub conditions: Option<NetworkConditions>,
+    /// file the exchanged messages are written to
+    pub recording: Option<PathBuf>,
+    /// agrees on keys during the handshake and encrypts every packet
+    pub encrypted: bool,
 }
 
 impl RemoteInfo {
-    #[allow(dead_code)]
-    pub fn new(ip: String, port: u16) -> Self {
-        Self { ip, port }
+    /// network conditions and recording are picked up from the environment, see
+    /// `NETWORK_CONDITIONS_VAR` and `RECORDING_VAR`
+    pub fn new(host: String, port: u16) -> Self {
+        Self {
+            host,
+            port,
+            transport: TransportKind::default(),
+            conditions: NetworkConditions::from_env(),
+            recording: Recorder::path_from_env(),
+            encrypted: true,
+        }
+    }
+
+    /// address in a form the socket api resolves, ipv6 addresses are put in brackets
+    pub fn address(&self) -> String {
+        if self.host.contains(':') {
+            format!("[{}]:{}", self.host, self.port)
+        } else {
+            format!("{}:{}", self.host, self.port)
+        }
+    }
+}
+
+impl fmt::Display for RemoteInfo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.transport {
+            TransportKind::Udp => write!(f, "{}", self.address()),
+            transport => write!(f, "{}://{}", transport, self.address()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RemoteInfoError {
+    Empty,
+    UnknownTransport(String),
+    InvalidHost(String),
+    InvalidPort(String),
+    /// an ipv6 address opened a bracket it never closed
+    UnclosedBracket,
+}
+
+impl fmt::Display for RemoteInfoError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RemoteInfoError::Empty => write!(f, "the address is empty"),
+            RemoteInfoError::UnknownTransport(transport) => write!(
+                f,
+                "unknown transport \"{}\", expected udp or tcp",
+                transport
+            ),
+            RemoteInfoError::InvalidHost(host) => write!(f, "\"{}\" is not a valid host", host),
+            RemoteInfoError::InvalidPort(port) => write!(
+                f,
+                "\"{}\" is not a valid port, expected a number between 1 and 65535",
+                port
+            ),
+            RemoteInfoError::UnclosedBracket => write!(f, "missing ] after the ipv6 address"),
+        }
+    }
+}
+
+impl Error for RemoteInfoError {}
+
+/// Parses `[transport://]host[:port]`, where the host is a hostname, an ipv4 address or an ipv6
+/// address. An ipv6 address followed by a port has to be put in brackets.
+impl FromStr for RemoteInfo {
+    type Err = RemoteInfoError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+
+        if s.is_empty() {
+            return Err(RemoteInfoError::Empty);
+        }
+
+        let (transport, address) = match s.find("://") {
+            Some(index) => {
+                let transport = match s[..index].to_lowercase().as_str() {
+                    "udp" => TransportKind::Udp,
+                    "tcp" => TransportKind::Tcp,
+                    _ => return Err(RemoteInfoError::UnknownTransport(s[..index].to_string())),
+                };
+
+                (transport, &s[index + 3..])
+            }
+            None => (TransportKind::default(), s),
+        };
+
+        let (host, port) = split_host_port(address)?;
+
+        let port = match port {
+            Some(port) => match port.parse::<u16>() {
+                Ok(port) if port != 0 => port,
+                _ => return Err(RemoteInfoError::InvalidPort(port.to_string())),
+            },
+            None => DEFAULT_PORT,
+        };
+
+        let mut info = RemoteInfo::new(host.to_string(), port);
+        info.transport = transport;
+
+        Ok(info)
+    }
+}
+
+fn split_host_port(address: &str) -> Result<(&str, Option<&str>), RemoteInfoError> {
+    if address.is_empty() {
+        return Err(RemoteInfoError::Empty);
+    }
+
+    if address.starts_with('[') {
+        let end = address.find(']').ok_or(RemoteInfoError::UnclosedBracket)?;
+        let host = &address[1..end];
+
+        if host.parse::<Ipv6Addr>().is_err() {
+            return Err(RemoteInfoError::InvalidHost(host.to_string()));
+        }
+
+        return match &address[end + 1..] {
+            "" => Ok((host, None)),
+            rest if rest.starts_with(':') => Ok((host, Some(&rest[1..]))),
+            rest => Err(RemoteInfoError::InvalidPort(rest.to_string())),
+        };
+    }
+
+    // more than one colon can only be a bare ipv6 address, which cannot carry a port
+    if address.matches(':').count() > 1 {
+        return match address.parse::<Ipv6Addr>() {
+            Ok(_) => Ok((address, None)),
+            Err(_) => Err(RemoteInfoError::InvalidHost(address.to_string())),
+        };
+    }
+
+    let (host, port) = match address.find(':') {
+        Some(index) => (&address[..index], Some(&address[index + 1..])),
+        None => (address, None),
+    };
+
+    if !is_valid_hostname(host) {
+        return Err(RemoteInfoError::InvalidHost(host.to_string()));
+    }
+
+    Ok((host, port))
+}
+
+/// hostnames are dot separated labels of letters, digits and hyphens, which covers ipv4
+/// addresses as well
+fn is_valid_hostname(host: &str) -> bool {
+    let host = host.strip_suffix('.').unwrap_or(host);
+
+    !host.is_empty()
+        && host.len() <= MAX_HOSTNAME_LENGTH
+        && host.split('.').all(|label| {
+            !label.is_empty()
+                && label.len() <= MAX_LABEL_LENGTH
+                && !label.starts_with('-')
+                && !label.ends_with('-')
+                && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(s: &str) -> Result<(String, u16, TransportKind), RemoteInfoError> {
+        s.parse::<RemoteInfo>()
+            .map(|info| (info.host, info.port, info.transport))
+    }
+
+    fn udp(host: &str, port: u16) -> Result<(String, u16, TransportKind), RemoteInfoError> {
+        Ok((host.to_string(), port, TransportKind::Udp))
+    }
+
+    #[test]
+    fn defaults_the_port() {
+        assert_eq!(parse("127.0.0.1"), udp("127.0.0.1", DEFAULT_PORT));
+        assert_eq!(parse("127.0.0.1:4000"), udp("127.0.0.1", 4000));
+    }
+
+    #[test]
+    fn parses_the_transport() {
+        assert_eq!(parse("udp://127.0.0.1:4000"), udp("127.0.0.1", 4000));
+        assert_eq!(
+            parse("TCP://127.0.0.1"),
+            Ok((String::from("127.0.0.1"), DEFAULT_PORT, TransportKind::Tcp))
+        );
+        assert_eq!(
+            parse("quic://127.0.0.1"),
+            Err(RemoteInfoError::UnknownTransport(String::from("quic")))
+        );
+    }
+
+    #[test]
+    fn parses_ipv6_addresses() {
+        assert_eq!(parse("[::1]:4000"), udp("::1", 4000));
+        assert_eq!(parse("[::1]"), udp("::1", DEFAULT_PORT));
+        assert_eq!(parse("fe80::1"), udp("fe80::1", DEFAULT_PORT));
+        assert_eq!(parse("[::1"), Err(RemoteInfoError::UnclosedBracket));
+        assert_eq!(
+            parse("[::1]4000"),
+            Err(RemoteInfoError::InvalidPort(String::from("4000")))
+        );
+        assert_eq!(
+            parse("fe80::1:4000:"),
+            Err(RemoteInfoError::InvalidHost(String::from("fe80::1:4000:")))
+        );
+    }
+
+    #[test]
+    fn parses_hostnames() {
+        assert_eq!(
+            parse("play.example.com:4000"),
+            udp("play.example.com", 4000)
+        );
+        assert_eq!(
+            parse("-play.example.com"),
+            Err(RemoteInfoError::InvalidHost(String::from(
+                "-play.example.com"
+            )))
+        );
+        assert_eq!(parse(" "), Err(RemoteInfoError::Empty));
+    }
+
+    #[test]
+    fn refuses_invalid_ports() {
+        for port in ["0", "65536", "port", ""].iter() {
+            assert_eq!(
+                parse(&format!("127.0.0.1:{}", port)),
+                Err(RemoteInfoError::InvalidPort(port.to_string()))
+            );
+        }
+    }
+
+    #[test]
+    fn displays_the_address_it_parsed() {
+        for address in ["127.0.0.1:4000", "tcp://[::1]:4000"].iter() {
+            assert_eq!(address.parse::<RemoteInfo>().unwrap().to_string(), *address);
+        }
     }
 }
@@ -0,0 +1,68 @@
+use crate::network::RemoteInfo;
+
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+const SERVER_LIST_FILE: &'static str = "servers.json";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SavedServer {
+    pub name: String,
+    /// kept as typed, parsed into a `RemoteInfo` when connecting
+    pub address: String,
+}
+
+/// Servers the player saved, stored as json in the user's data directory
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ServerList {
+    pub servers: Vec<SavedServer>,
+}
+
+impl ServerList {
+    /// `~/.roadrunner/servers.json`, or next to the executable when there is no home directory
+    pub fn default_path() -> PathBuf {
+        env::var_os("HOME")
+            .or_else(|| env::var_os("APPDATA"))
+            .map(|home| PathBuf::from(home).join(".roadrunner"))
+            .unwrap_or_default()
+            .join(SERVER_LIST_FILE)
+    }
+
+    /// a missing file is an empty list
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        match fs::read_to_string(path) {
+            Ok(data) => serde_json::from_str(&data)
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err)),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(err) => Err(err),
+        }
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        if let Some(parent) = path.as_ref().parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let data = serde_json::to_string_pretty(self)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+
+        fs::write(path, data)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&SavedServer> {
+        self.servers.iter().find(|server| server.name == name)
+    }
+
+    /// adds the server, or changes its address when the name is already taken
+    pub fn insert(&mut self, name: String, info: &RemoteInfo) {
+        let address = info.to_string();
+
+        match self.servers.iter_mut().find(|server| server.name == name) {
+            Some(server) => server.address = address,
+            None => self.servers.push(SavedServer { name, address }),
+        }
+    }
+}
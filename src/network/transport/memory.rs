@@ -0,0 +1,46 @@
+use crate::network::transport::Transport;
+
+use std::io;
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+
+/// In process transport, one end is handed to the client and the other one to whatever plays
+/// the server, a test or a server thread
+pub struct MemoryTransport {
+    sender: Sender<Vec<u8>>,
+    receiver: Receiver<Vec<u8>>,
+}
+
+impl MemoryTransport {
+    /// creates two connected ends
+    pub fn pair() -> (Self, Self) {
+        let (a_sender, b_receiver) = mpsc::channel();
+        let (b_sender, a_receiver) = mpsc::channel();
+
+        (
+            Self {
+                sender: a_sender,
+                receiver: a_receiver,
+            },
+            Self {
+                sender: b_sender,
+                receiver: b_receiver,
+            },
+        )
+    }
+}
+
+impl Transport for MemoryTransport {
+    fn send(&mut self, data: &[u8]) -> io::Result<()> {
+        self.sender
+            .send(data.to_vec())
+            .map_err(|_| io::ErrorKind::BrokenPipe.into())
+    }
+
+    fn receive(&mut self) -> io::Result<Option<Vec<u8>>> {
+        match self.receiver.try_recv() {
+            Ok(data) => Ok(Some(data)),
+            Err(TryRecvError::Empty) => Ok(None),
+            Err(TryRecvError::Disconnected) => Err(io::ErrorKind::BrokenPipe.into()),
+        }
+    }
+}
@@ -0,0 +1,61 @@
+mod memory;
+mod simulated;
+mod tcp;
+mod udp;
+
+pub use self::memory::MemoryTransport;
+pub use self::simulated::{NetworkConditions, SimulatedTransport};
+pub use self::tcp::TcpTransport;
+pub use self::udp::UdpTransport;
+
+use crate::network::RemoteInfo;
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::io;
+
+/// Moves whole datagrams between the client and the server
+///
+/// Implementations never block, `receive` returns `None` when nothing is waiting. Datagrams can
+/// be lost or reordered, the channel on top takes care of reliability whatever the transport.
+pub trait Transport: Send {
+    fn send(&mut self, data: &[u8]) -> io::Result<()>;
+
+    fn receive(&mut self) -> io::Result<Option<Vec<u8>>>;
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub enum TransportKind {
+    Udp,
+    /// for networks that block udp, datagrams are framed with their length
+    Tcp,
+}
+
+impl Default for TransportKind {
+    fn default() -> Self {
+        TransportKind::Udp
+    }
+}
+
+impl fmt::Display for TransportKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TransportKind::Udp => write!(f, "udp"),
+            TransportKind::Tcp => write!(f, "tcp"),
+        }
+    }
+}
+
+/// opens a transport to the remote, degraded by the simulator when it has network conditions
+pub fn open(info: &RemoteInfo) -> io::Result<Box<dyn Transport>> {
+    let address = info.address();
+    let transport: Box<dyn Transport> = match info.transport {
+        TransportKind::Udp => Box::new(UdpTransport::connect(&address)?),
+        TransportKind::Tcp => Box::new(TcpTransport::connect(&address)?),
+    };
+
+    Ok(match info.conditions.clone() {
+        Some(conditions) => Box::new(SimulatedTransport::new(transport, conditions)),
+        None => transport,
+    })
+}
@@ -0,0 +1,203 @@
+use crate::network::transport::Transport;
+use crate::utils::Random;
+
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::io;
+use std::time::{Duration, Instant};
+
+/// environment variable holding the json encoded conditions to simulate
+pub const NETWORK_CONDITIONS_VAR: &'static str = "ROADRUNNER_NETWORK_CONDITIONS";
+/// time between two reports of what the simulator did
+const REPORT_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Degradations applied to the traffic in both directions
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NetworkConditions {
+    pub seed: u64,
+    /// one way delay in milliseconds
+    pub latency: u64,
+    /// maximum variation of the delay in milliseconds, in either direction
+    pub jitter: u64,
+    /// probabilities between 0 and 1
+    pub loss: f32,
+    pub duplication: f32,
+    /// a reordered datagram is held back for an extra `latency`
+    pub reordering: f32,
+}
+
+impl Default for NetworkConditions {
+    fn default() -> Self {
+        Self {
+            seed: 0,
+            latency: 0,
+            jitter: 0,
+            loss: 0.0,
+            duplication: 0.0,
+            reordering: 0.0,
+        }
+    }
+}
+
+impl NetworkConditions {
+    /// reads the conditions from `NETWORK_CONDITIONS_VAR`, none when it is not set
+    pub fn from_env() -> Option<Self> {
+        let value = env::var(NETWORK_CONDITIONS_VAR).ok()?;
+
+        match serde_json::from_str(&value) {
+            Ok(conditions) => Some(conditions),
+            Err(err) => {
+                error!("<netsim> invalid {}: {}", NETWORK_CONDITIONS_VAR, err);
+                None
+            }
+        }
+    }
+}
+
+#[derive(Default)]
+struct Statistics {
+    datagrams: u64,
+    dropped: u64,
+    duplicated: u64,
+    reordered: u64,
+}
+
+struct DelayedDatagram {
+    deliver_at: Instant,
+    data: Vec<u8>,
+}
+
+/// One direction of the simulated link
+struct Link {
+    name: &'static str,
+    random: Random,
+    queue: Vec<DelayedDatagram>,
+    statistics: Statistics,
+}
+
+impl Link {
+    fn new(name: &'static str, seed: u64) -> Self {
+        Self {
+            name,
+            random: Random::new(seed),
+            queue: Vec::new(),
+            statistics: Statistics::default(),
+        }
+    }
+
+    fn push(&mut self, data: Vec<u8>, conditions: &NetworkConditions, now: Instant) {
+        self.statistics.datagrams += 1;
+
+        if self.random.chance(conditions.loss) {
+            self.statistics.dropped += 1;
+            return;
+        }
+
+        let copies = if self.random.chance(conditions.duplication) {
+            self.statistics.duplicated += 1;
+            2
+        } else {
+            1
+        };
+
+        for _ in 0..copies {
+            let jitter = conditions.jitter as i64;
+            let mut delay = (conditions.latency as i64 + self.random.range(-jitter, jitter)).max(0);
+
+            if self.random.chance(conditions.reordering) {
+                self.statistics.reordered += 1;
+                delay += conditions.latency.max(1) as i64;
+            }
+
+            self.queue.push(DelayedDatagram {
+                deliver_at: now + Duration::from_millis(delay as u64),
+                data: data.clone(),
+            });
+        }
+    }
+
+    /// removes the datagram that is due the earliest, if any
+    fn pop(&mut self, now: Instant) -> Option<Vec<u8>> {
+        let index = self
+            .queue
+            .iter()
+            .enumerate()
+            .filter(|(_, datagram)| datagram.deliver_at <= now)
+            .min_by_key(|(_, datagram)| datagram.deliver_at)
+            .map(|(index, _)| index)?;
+
+        Some(self.queue.remove(index).data)
+    }
+
+    fn report(&self) {
+        info!(
+            "<netsim> {}: {} datagrams, {} dropped, {} duplicated, {} reordered, {} in flight",
+            self.name,
+            self.statistics.datagrams,
+            self.statistics.dropped,
+            self.statistics.duplicated,
+            self.statistics.reordered,
+            self.queue.len()
+        );
+    }
+}
+
+/// Wraps a transport and degrades its traffic according to seeded `NetworkConditions`
+pub struct SimulatedTransport {
+    inner: Box<dyn Transport>,
+    conditions: NetworkConditions,
+    outgoing: Link,
+    incoming: Link,
+    last_report: Instant,
+}
+
+impl SimulatedTransport {
+    pub fn new(inner: Box<dyn Transport>, conditions: NetworkConditions) -> Self {
+        info!("<netsim> simulating {:?}", conditions);
+
+        Self {
+            inner,
+            outgoing: Link::new("outgoing", conditions.seed),
+            incoming: Link::new("incoming", conditions.seed.wrapping_add(1)),
+            conditions,
+            last_report: Instant::now(),
+        }
+    }
+
+    /// hands the datagrams that waited long enough to the real transport
+    fn release(&mut self, now: Instant) -> io::Result<()> {
+        while let Some(data) = self.outgoing.pop(now) {
+            self.inner.send(&data)?;
+        }
+
+        if now.duration_since(self.last_report) >= REPORT_INTERVAL {
+            self.outgoing.report();
+            self.incoming.report();
+            self.last_report = now;
+        }
+
+        Ok(())
+    }
+}
+
+impl Transport for SimulatedTransport {
+    fn send(&mut self, data: &[u8]) -> io::Result<()> {
+        let now = Instant::now();
+
+        self.outgoing.push(data.to_vec(), &self.conditions, now);
+        self.release(now)
+    }
+
+    fn receive(&mut self) -> io::Result<Option<Vec<u8>>> {
+        let now = Instant::now();
+
+        self.release(now)?;
+
+        while let Some(data) = self.inner.receive()? {
+            self.incoming.push(data, &self.conditions, now);
+        }
+
+        Ok(self.incoming.pop(now))
+    }
+}
@@ -0,0 +1,123 @@
+use crate::network::transport::Transport;
+
+use std::convert::TryInto;
+use std::io::{self, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+/// every datagram is preceded by its length as a big endian u32
+const HEADER_SIZE: usize = 4;
+const MAX_FRAME_SIZE: usize = 65535;
+const READ_SIZE: usize = 8192;
+/// connecting happens on the game thread, it should not freeze it for long
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Stream transport, datagrams are delimited with a length prefix
+pub struct TcpTransport {
+    stream: TcpStream,
+    incoming: Vec<u8>,
+    /// bytes the socket did not accept yet
+    outgoing: Vec<u8>,
+}
+
+impl TcpTransport {
+    pub fn connect(address: &str) -> io::Result<Self> {
+        let address = address
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "address did not resolve"))?;
+
+        Self::new(TcpStream::connect_timeout(&address, CONNECT_TIMEOUT)?)
+    }
+
+    pub fn new(stream: TcpStream) -> io::Result<Self> {
+        stream.set_nonblocking(true)?;
+        stream.set_nodelay(true)?;
+
+        Ok(Self {
+            stream,
+            incoming: Vec::new(),
+            outgoing: Vec::new(),
+        })
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        while !self.outgoing.is_empty() {
+            match self.stream.write(&self.outgoing) {
+                Ok(0) => return Err(io::ErrorKind::WriteZero.into()),
+                Ok(size) => {
+                    self.outgoing.drain(..size);
+                }
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => break,
+                Err(err) => return Err(err),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// removes the first complete frame from the incoming bytes
+    fn next_frame(&mut self) -> io::Result<Option<Vec<u8>>> {
+        if self.incoming.len() < HEADER_SIZE {
+            return Ok(None);
+        }
+
+        let size = u32::from_be_bytes(self.incoming[..HEADER_SIZE].try_into().unwrap()) as usize;
+
+        if size > MAX_FRAME_SIZE {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("frame of {} bytes is too large", size),
+            ));
+        }
+
+        if self.incoming.len() < HEADER_SIZE + size {
+            return Ok(None);
+        }
+
+        let frame = self.incoming[HEADER_SIZE..HEADER_SIZE + size].to_vec();
+        self.incoming.drain(..HEADER_SIZE + size);
+
+        Ok(Some(frame))
+    }
+}
+
+impl Transport for TcpTransport {
+    fn send(&mut self, data: &[u8]) -> io::Result<()> {
+        if data.len() > MAX_FRAME_SIZE {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("datagram of {} bytes is too large", data.len()),
+            ));
+        }
+
+        self.outgoing
+            .extend_from_slice(&(data.len() as u32).to_be_bytes());
+        self.outgoing.extend_from_slice(data);
+
+        self.flush()
+    }
+
+    fn receive(&mut self) -> io::Result<Option<Vec<u8>>> {
+        // whatever a previous send could not write
+        self.flush()?;
+
+        if let Some(frame) = self.next_frame()? {
+            return Ok(Some(frame));
+        }
+
+        let mut buffer = [0; READ_SIZE];
+
+        loop {
+            match self.stream.read(&mut buffer) {
+                Ok(0) => return Err(io::ErrorKind::UnexpectedEof.into()),
+                Ok(size) => self.incoming.extend_from_slice(&buffer[..size]),
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => break,
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => return Err(err),
+            }
+        }
+
+        self.next_frame()
+    }
+}
@@ -0,0 +1,51 @@
+use crate::network::transport::Transport;
+
+use std::io;
+use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+
+const MAX_DATAGRAM_SIZE: usize = 65535;
+
+pub struct UdpTransport {
+    socket: UdpSocket,
+    buffer: Vec<u8>,
+}
+
+impl UdpTransport {
+    pub fn connect(address: &str) -> io::Result<Self> {
+        let target = address.to_socket_addrs()?.next().ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::AddrNotAvailable,
+                format!("could not resolve {}", address),
+            )
+        })?;
+
+        // the socket has to be of the same family as the server
+        let local = match target {
+            SocketAddr::V4(_) => "0.0.0.0:0",
+            SocketAddr::V6(_) => "[::]:0",
+        };
+
+        let socket = UdpSocket::bind(local)?;
+        socket.set_nonblocking(true)?;
+        socket.connect(target)?;
+
+        Ok(Self {
+            socket,
+            buffer: vec![0; MAX_DATAGRAM_SIZE],
+        })
+    }
+}
+
+impl Transport for UdpTransport {
+    fn send(&mut self, data: &[u8]) -> io::Result<()> {
+        self.socket.send(data).map(|_| ())
+    }
+
+    fn receive(&mut self) -> io::Result<Option<Vec<u8>>> {
+        match self.socket.recv(&mut self.buffer) {
+            Ok(size) => Ok(Some(self.buffer[..size].to_vec())),
+            Err(err) if err.kind() == io::ErrorKind::WouldBlock => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+}
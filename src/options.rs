@@ -0,0 +1,260 @@
+use crate::game::entity::{is_valid_username, MAX_USERNAME_LENGTH};
+use crate::game::{GameType, DEFAULT_INTERPOLATION_DELAY};
+use crate::network::{Discovery, RemoteInfo, ServerList, ServerTime};
+
+use std::env;
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// time given to the servers of the local network to answer
+const LAN_DISCOVERY_TIMEOUT: Duration = Duration::from_secs(1);
+/// username used when none is given and the one of the system is not valid
+const DEFAULT_USERNAME: &'static str = "Player";
+
+const USAGE: &'static str = "\
+USAGE:
+    roadrunner [OPTIONS]
+
+Starts a local game when no server is given.
+
+OPTIONS:
+    --connect <ADDRESS>    join the server at [udp://|tcp://]host[:port]
+    --save <NAME>          save the --connect address in the server list
+    --server <NAME>        join a server of the server list
+    --servers              print the server list
+    --lan                  join the first server found on the local network
+    --replay <FILE>        play a recording back
+    --name <USERNAME>      name shown to the other players, defaults to the system user
+    --insecure             do not encrypt the connection, for servers that do not support it
+    --fps <LIMIT>          vsync (default), uncapped or a number of frames per second
+    --interpolation-delay <MS>
+                           how far in the past other players are shown, defaults to 100
+    -h, --help             print this message";
+
+/// How often frames are drawn, the simulation runs at the same pace whatever the frame rate
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum FrameLimit {
+    /// one frame per refresh of the screen
+    VSync,
+    Uncapped,
+    /// frames per second
+    Target(u32),
+}
+
+pub struct Options {
+    pub game_type: GameType,
+    pub username: String,
+    pub frame_limit: FrameLimit,
+    /// in milliseconds, a longer delay hides more network jitter but shows older positions
+    pub interpolation_delay: ServerTime,
+}
+
+/// Options that apply whatever the game type
+struct Settings {
+    username: Option<String>,
+    frame_limit: FrameLimit,
+    interpolation_delay: ServerTime,
+}
+
+/// Reads the game to start from the command line, none when there is nothing to play
+pub fn from_args<I: Iterator<Item = String>>(args: I) -> Result<Option<Options>, String> {
+    let mut settings = Settings {
+        username: None,
+        frame_limit: FrameLimit::VSync,
+        interpolation_delay: DEFAULT_INTERPOLATION_DELAY,
+    };
+
+    let game_type = match game_type_from_args(args, &mut settings)? {
+        Some(game_type) => game_type,
+        None => return Ok(None),
+    };
+
+    let username = match settings.username {
+        Some(username) if is_valid_username(&username) => username,
+        Some(username) => {
+            return Err(format!(
+                "invalid username \"{}\", expected up to {} letters, digits or underscores",
+                username, MAX_USERNAME_LENGTH
+            ))
+        }
+        None => system_username(),
+    };
+
+    Ok(Some(Options {
+        game_type,
+        username,
+        frame_limit: settings.frame_limit,
+        interpolation_delay: settings.interpolation_delay,
+    }))
+}
+
+fn game_type_from_args<I: Iterator<Item = String>>(
+    mut args: I,
+    settings: &mut Settings,
+) -> Result<Option<GameType>, String> {
+    let mut connect = None;
+    let mut save = None;
+    let mut server = None;
+    let mut lan = false;
+    let mut replay = None;
+    let mut insecure = false;
+
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .ok_or_else(|| format!("{} expects a value\n\n{}", arg, USAGE))
+        };
+
+        match arg.as_str() {
+            "--connect" => connect = Some(value()?),
+            "--save" => save = Some(value()?),
+            "--server" => server = Some(value()?),
+            "--replay" => replay = Some(PathBuf::from(value()?)),
+            "--name" => settings.username = Some(value()?),
+            "--fps" => settings.frame_limit = parse_frame_limit(&value()?)?,
+            "--interpolation-delay" => {
+                let delay = value()?;
+
+                settings.interpolation_delay = delay
+                    .parse()
+                    .map_err(|_| format!("invalid interpolation delay \"{}\"", delay))?;
+            }
+            "--lan" => lan = true,
+            "--insecure" => insecure = true,
+            "--servers" => {
+                print_servers()?;
+                return Ok(None);
+            }
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return Ok(None);
+            }
+            _ => return Err(format!("unknown argument {}\n\n{}", arg, USAGE)),
+        }
+    }
+
+    let choices = [connect.is_some(), server.is_some(), lan, replay.is_some()];
+
+    if choices.iter().filter(|choice| **choice).count() > 1 {
+        return Err(String::from(
+            "--connect, --server, --lan and --replay cannot be used together",
+        ));
+    }
+
+    if save.is_some() && connect.is_none() {
+        return Err(String::from("--save needs a --connect address"));
+    }
+
+    if insecure && connect.is_none() && server.is_none() && !lan {
+        return Err(String::from("--insecure only applies to a remote server"));
+    }
+
+    if let Some(path) = replay {
+        return Ok(Some(GameType::Replay { path }));
+    }
+
+    let mut info = if let Some(address) = connect {
+        let info = parse_address(&address)?;
+
+        if let Some(name) = save {
+            save_server(name, &info)?;
+        }
+
+        info
+    } else if let Some(name) = server {
+        let list = load_servers()?;
+        let saved = list
+            .get(&name)
+            .ok_or_else(|| format!("there is no server named \"{}\"", name))?;
+
+        parse_address(&saved.address)?
+    } else if lan {
+        find_lan_server()?
+    } else {
+        return Ok(Some(GameType::Local));
+    };
+
+    info.encrypted = !insecure;
+
+    Ok(Some(GameType::Remote { info }))
+}
+
+fn system_username() -> String {
+    env::var("USER")
+        .or_else(|_| env::var("USERNAME"))
+        .ok()
+        .filter(|username| is_valid_username(username))
+        .unwrap_or_else(|| String::from(DEFAULT_USERNAME))
+}
+
+fn parse_frame_limit(limit: &str) -> Result<FrameLimit, String> {
+    match limit {
+        "vsync" => Ok(FrameLimit::VSync),
+        "uncapped" => Ok(FrameLimit::Uncapped),
+        _ => match limit.parse() {
+            Ok(fps) if fps > 0 => Ok(FrameLimit::Target(fps)),
+            _ => Err(format!(
+                "invalid frame limit \"{}\", expected vsync, uncapped or a number of frames per second",
+                limit
+            )),
+        },
+    }
+}
+
+fn parse_address(address: &str) -> Result<RemoteInfo, String> {
+    address
+        .parse()
+        .map_err(|err| format!("invalid address \"{}\": {}", address, err))
+}
+
+fn load_servers() -> Result<ServerList, String> {
+    let path = ServerList::default_path();
+
+    ServerList::load(&path).map_err(|err| format!("could not read {}: {}", path.display(), err))
+}
+
+fn save_server(name: String, info: &RemoteInfo) -> Result<(), String> {
+    let path = ServerList::default_path();
+    let mut list = load_servers()?;
+
+    list.insert(name, info);
+    list.save(&path)
+        .map_err(|err| format!("could not write {}: {}", path.display(), err))
+}
+
+fn print_servers() -> Result<(), String> {
+    let list = load_servers()?;
+
+    if list.servers.is_empty() {
+        println!("no saved server, add one with --connect <ADDRESS> --save <NAME>");
+    }
+
+    for server in list.servers.iter() {
+        println!("{}\t{}", server.name, server.address);
+    }
+
+    Ok(())
+}
+
+fn find_lan_server() -> Result<RemoteInfo, String> {
+    let servers = Discovery::broadcast()
+        .map_err(|err| format!("could not search the local network: {}", err))?
+        .wait(LAN_DISCOVERY_TIMEOUT);
+
+    for server in servers.iter() {
+        println!(
+            "{} ({}/{} players, {}ms) at {}",
+            server.description.name,
+            server.description.players,
+            server.description.max_players,
+            server.latency.as_millis(),
+            server.info
+        );
+    }
+
+    servers
+        .into_iter()
+        .find(|server| server.is_compatible())
+        .map(|server| server.info)
+        .ok_or_else(|| String::from("no compatible server found on the local network"))
+}
@@ -8,8 +8,9 @@ pub struct Display {
 }
 
 impl Display {
+    /// `vsync` waits for the screen to refresh before swapping the buffers
     #[allow(unused_must_use)]
-    pub fn new(title: &'static str, event_loop: &EventLoop<()>) -> Self {
+    pub fn new(title: &'static str, event_loop: &EventLoop<()>, vsync: bool) -> Self {
         let monitor = event_loop.primary_monitor().unwrap();
         let psize = monitor.size();
         let size = Size::Physical(psize);
@@ -21,7 +22,7 @@ impl Display {
             .with_inner_size(size);
         let context_builder = ContextBuilder::new()
             .with_srgb(true)
-            .with_vsync(true)
+            .with_vsync(vsync)
             .with_depth_buffer(24)
             .with_gl_profile(GlProfile::Core)
             .with_gl(GlRequest::Specific(Api::OpenGl, (4, 1)));
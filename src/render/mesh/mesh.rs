@@ -90,6 +90,22 @@ impl Mesh {
         self.vbo_count += 1;
     }
 
+    /// draws the indices as pairs of line ends instead of triangles
+    pub fn draw_lines(&self) {
+        self.bind();
+
+        unsafe {
+            gl::DrawElements(
+                gl::LINES,
+                self.index_count as GLint,
+                gl::UNSIGNED_INT,
+                ptr::null(),
+            );
+        }
+
+        self.unbind();
+    }
+
     fn add_ebo(&mut self, indices: &Vec<GLuint>) {
         let mut ebo: GLuint = 0;
 
@@ -1,84 +1,112 @@
-use crate::ops::{Bindable, Drawable};
 use crate::render::mesh::Mesh;
 
 use math::vector::Vector3;
 
+/// Pieces of a player model, each one is rotated on its own by the player renderer
 pub struct PlayerMesh {
-    mesh: Mesh,
+    /// body, with the pivot at the feet
+    pub body: Mesh,
+    /// head, with the pivot at the eyes
+    pub head: Mesh,
+    /// front face of the head, shows where the player looks
+    pub visor: Mesh,
 }
 
 impl PlayerMesh {
     pub fn new() -> Self {
-        let vertices = vec![
-            Vector3 {
-                x: 0.0,
-                y: 2.0,
-                z: 0.0,
-            },
-            Vector3 {
-                x: 1.0,
-                y: 2.0,
-                z: 0.0,
-            },
-            Vector3 {
-                x: 1.0,
-                y: 0.0,
-                z: 0.0,
-            },
-            Vector3 {
-                x: 0.0,
-                y: 0.0,
-                z: 0.0,
-            },
-            Vector3 {
-                x: 1.0,
-                y: 2.0,
-                z: 1.0,
-            },
-            Vector3 {
-                x: 0.0,
-                y: 2.0,
-                z: 1.0,
-            },
-            Vector3 {
-                x: 0.0,
-                y: 0.0,
-                z: 1.0,
-            },
-            Vector3 {
-                x: 1.0,
-                y: 0.0,
-                z: 1.0,
-            },
-        ];
-
-        let indices = vec![
-            0, 3, 1, 3, 2, 1, // front
-            5, 0, 4, 0, 1, 4, // top
-            3, 6, 2, 6, 7, 2, // bottom
-            1, 2, 4, 2, 7, 4, // right
-            0, 5, 3, 5, 6, 3, // left
-            4, 7, 5, 7, 6, 5, // back
-        ];
-
         Self {
-            mesh: Mesh::new(&vertices, &indices),
+            body: cuboid(
+                Vector3 {
+                    x: -0.3,
+                    y: 0.0,
+                    z: -0.2,
+                },
+                Vector3 {
+                    x: 0.3,
+                    y: 1.25,
+                    z: 0.2,
+                },
+            ),
+            head: cuboid(
+                Vector3 {
+                    x: -0.25,
+                    y: -0.25,
+                    z: -0.25,
+                },
+                Vector3 {
+                    x: 0.25,
+                    y: 0.25,
+                    z: 0.25,
+                },
+            ),
+            visor: cuboid(
+                Vector3 {
+                    x: -0.2,
+                    y: -0.05,
+                    z: 0.25,
+                },
+                Vector3 {
+                    x: 0.2,
+                    y: 0.1,
+                    z: 0.3,
+                },
+            ),
         }
     }
 }
 
-impl Drawable for PlayerMesh {
-    fn draw(&self) {
-        self.mesh.draw();
-    }
-}
+fn cuboid(min: Vector3, max: Vector3) -> Mesh {
+    let vertices = vec![
+        Vector3 {
+            x: min.x,
+            y: max.y,
+            z: min.z,
+        },
+        Vector3 {
+            x: max.x,
+            y: max.y,
+            z: min.z,
+        },
+        Vector3 {
+            x: max.x,
+            y: min.y,
+            z: min.z,
+        },
+        Vector3 {
+            x: min.x,
+            y: min.y,
+            z: min.z,
+        },
+        Vector3 {
+            x: max.x,
+            y: max.y,
+            z: max.z,
+        },
+        Vector3 {
+            x: min.x,
+            y: max.y,
+            z: max.z,
+        },
+        Vector3 {
+            x: min.x,
+            y: min.y,
+            z: max.z,
+        },
+        Vector3 {
+            x: max.x,
+            y: min.y,
+            z: max.z,
+        },
+    ];
 
-impl Bindable for PlayerMesh {
-    fn bind(&self) {
-        self.mesh.bind();
-    }
+    let indices = vec![
+        0, 3, 1, 3, 2, 1, // front
+        5, 0, 4, 0, 1, 4, // top
+        3, 6, 2, 6, 7, 2, // bottom
+        1, 2, 4, 2, 7, 4, // right
+        0, 5, 3, 5, 6, 3, // left
+        4, 7, 5, 7, 6, 5, // back
+    ];
 
-    fn unbind(&self) {
-        self.mesh.unbind();
-    }
+    Mesh::new(&vertices, &indices)
 }
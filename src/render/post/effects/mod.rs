@@ -1,5 +1,7 @@
 mod fxaa;
 mod identity;
+mod underwater;
 
 pub use self::fxaa::FXAAPostProcessing;
 pub use self::identity::IdentityPostProcessing;
+pub use self::underwater::UnderwaterPostProcessing;
@@ -0,0 +1,70 @@
+use crate::render::display::FrameBuffer;
+use crate::render::post::PostProcessingEffect;
+use crate::render::shaders::ShaderProgram;
+
+use math::vector::Vector3;
+
+const TINT: Vector3 = Vector3 {
+    x: 0.1,
+    y: 0.3,
+    z: 0.6,
+};
+/// how much of the tint replaces the image
+const TINT_STRENGTH: f32 = 0.4;
+
+/// Tints the image in blue while the camera is under water
+pub struct UnderwaterPostProcessing {
+    program: ShaderProgram,
+}
+
+impl UnderwaterPostProcessing {
+    pub fn new() -> Self {
+        let vertex_src: &'static str = r#"
+            #version 410 core
+
+            layout (location=0) in vec3 position;
+            layout (location=1) in vec2 uv_data;
+
+            out vec2 uv;
+
+            void main() {
+                uv = uv_data;
+                gl_Position = vec4(position, 1.0);
+            }
+        "#;
+
+        let fragment_src: &'static str = r#"
+            #version 410 core
+
+            in vec2 uv;
+            out vec4 color;
+
+            uniform sampler2D input_texture;
+            uniform vec3 tint;
+            uniform float strength;
+
+            void main() {
+                vec4 image = texture(input_texture, uv);
+                color = vec4(mix(image.rgb, tint, strength), image.a);
+            }
+        "#;
+
+        match ShaderProgram::new(vertex_src, fragment_src) {
+            Ok(program) => Self { program },
+            Err(err) => {
+                error!("could not compile shader program {}:{}", file!(), line!());
+                panic!("\n{}\n", err);
+            }
+        }
+    }
+}
+
+impl PostProcessingEffect for UnderwaterPostProcessing {
+    fn prepare(&self, src: &FrameBuffer) {
+        self.program.use_program();
+        self.program
+            .set_uniform_texture("input_texture", src.unit());
+        self.program.set_uniform_v3("tint", TINT);
+        self.program.set_uniform_f32("strength", TINT_STRENGTH);
+    }
+}
@@ -5,13 +5,21 @@ use crate::render::post::effects::*;
 use crate::render::post::PostProcessingEffect;
 
 #[allow(dead_code)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum PostProcessingEffectType {
     Identity,
     FXAA,
+    Underwater,
+}
+
+struct PostProcessingStage {
+    effect_type: PostProcessingEffectType,
+    effect: Box<dyn PostProcessingEffect>,
+    enabled: bool,
 }
 
 pub struct PostProcessingPipeline {
-    effects: Vec<Box<dyn PostProcessingEffect>>,
+    stages: Vec<PostProcessingStage>,
     screen: FrameBuffer,
     swap1: FrameBuffer,
     swap2: FrameBuffer,
@@ -21,7 +29,7 @@ pub struct PostProcessingPipeline {
 impl PostProcessingPipeline {
     pub fn new(width: usize, height: usize) -> Self {
         Self {
-            effects: Vec::new(),
+            stages: Vec::new(),
             swap1: FrameBuffer::new(width, height, 2, false),
             swap2: FrameBuffer::new(width, height, 1, false),
             screen: FrameBuffer::empty(width, height),
@@ -35,15 +43,37 @@ impl PostProcessingPipeline {
         self.screen = FrameBuffer::empty(width, height);
     }
 
-    pub fn add(&mut self, effect: PostProcessingEffectType) {
-        self.effects.push(match effect {
+    pub fn add(&mut self, effect_type: PostProcessingEffectType) {
+        let effect: Box<dyn PostProcessingEffect> = match effect_type {
             PostProcessingEffectType::Identity => Box::new(IdentityPostProcessing::new()),
             PostProcessingEffectType::FXAA => Box::new(FXAAPostProcessing::new()),
+            PostProcessingEffectType::Underwater => Box::new(UnderwaterPostProcessing::new()),
+        };
+
+        self.stages.push(PostProcessingStage {
+            effect_type,
+            effect,
+            enabled: true,
         });
     }
 
+    /// disabled effects stay in the pipeline but are skipped
+    pub fn set_enabled(&mut self, effect_type: PostProcessingEffectType, enabled: bool) {
+        for stage in self.stages.iter_mut() {
+            if stage.effect_type == effect_type {
+                stage.enabled = enabled;
+            }
+        }
+    }
+
     pub fn apply(&self, input: &FrameBuffer) {
-        let len = self.effects.len();
+        let effects = self
+            .stages
+            .iter()
+            .filter(|stage| stage.enabled)
+            .map(|stage| &stage.effect)
+            .collect::<Vec<_>>();
+        let len = effects.len();
 
         unsafe {
             gl::Disable(gl::DEPTH_TEST);
@@ -68,7 +98,7 @@ impl PostProcessingPipeline {
                 target = &self.screen;
             }
 
-            self.effects[i].prepare(source);
+            effects[i].prepare(source);
 
             source.bind_texture();
             target.bind();
@@ -1,4 +1,4 @@
-use crate::game::TextureDatabase;
+use crate::game::{load_block_registry, TextureDatabase};
 use crate::ops::{Bindable, Drawable};
 use crate::render::camera::Camera;
 use crate::render::mesh::chunk_mesh::{generate_mesh, ChunkMeshCollection};
@@ -12,13 +12,13 @@ use core::world::{World, LOAD_DISTANCE};
 use math::container::{Volume, AABB};
 use math::vector::Vector3;
 use std::collections::{HashSet, HashMap};
-use std::fs;
-use std::path::Path;
 use std::sync::mpsc::{channel, Receiver, Sender};
 use core::utils::ThreadPool;
 
 #[cfg(feature = "watchers")]
 use crate::utils::watcher::*;
+#[cfg(feature = "watchers")]
+use std::path::Path;
 
 // TODO: remove the dependancy to glutin from this file.
 use crate::input::InputHandler;
@@ -31,6 +31,13 @@ const FOG: Vector3 = Vector3 {
     y: 0.76,
     z: 1.0,
 };
+pub const UNDERWATER_FOG: Vector3 = Vector3 {
+    x: 0.05,
+    y: 0.18,
+    z: 0.4,
+};
+/// distance at which the fog hides everything under water
+const UNDERWATER_FOG_DISTANCE: f32 = 12.0;
 
 fn load_textures() -> TextureArray {
     let database = TextureDatabase::new();
@@ -52,11 +59,17 @@ pub struct ChunkRenderer {
     meshes: HashMap<ChunkGridCoordinate, ChunkMeshCollection>,
     block_registry: BlockRegistry,
     pub render_distance: u8,
+    /// the camera is under water, the fog gets thicker and bluer
+    pub underwater: bool,
 
     // Threading
     chunk_loading_chan: ChunkLoadingChannel,
     threadpool: ThreadPool,
     loading_chunks: HashSet<ChunkGridCoordinate>,
+    /// chunks whose mesh, drawn or being generated, no longer matches their blocks
+    stale_chunks: HashSet<ChunkGridCoordinate>,
+    /// chunks remeshed while a mesh was being generated for them, that mesh is dropped
+    outdated_jobs: HashSet<ChunkGridCoordinate>,
 
     #[cfg(feature = "watchers")]
     texture_watcher: Watcher,
@@ -113,14 +126,18 @@ impl ChunkRenderer {
             uniform vec3 camera_position;
             uniform vec3 fog_color;
             uniform uint render_distance;
+            uniform bool underwater;
+            uniform float underwater_fog_distance;
             
             vec4 get_color(uint id) {
                 return light * texture(diffuse_textures, vec3(uv, id));
             }
 
             vec4 apply_fog(vec4 diffuse) {
-                float fog_max = max(32.0, float(render_distance * 16));
-                float fog_min = max(16.0, fog_max - 64.0);
+                float fog_max = underwater ?
+                    underwater_fog_distance :
+                    max(32.0, float(render_distance * 16));
+                float fog_min = underwater ? 0.0 : max(16.0, fog_max - 64.0);
 
                 float distance = length(camera_position - world_position);
                 float fog = (distance - fog_min) / (fog_max - fog_min);
@@ -173,12 +190,7 @@ impl ChunkRenderer {
 
         let textures = load_textures();
 
-        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("res/data/blocks.json");
-        let path = path.to_str().unwrap();
-
-        let data =
-            fs::read_to_string(path).expect("<block_database> Could not read data from file");
-        let block_registry = BlockRegistry::new(serde_json::from_str(&data).unwrap());
+        let block_registry = load_block_registry();
 
         match ShaderProgram::new(vertex_src, fragment_src) {
             Ok(program) => Self {
@@ -187,10 +199,13 @@ impl ChunkRenderer {
                 meshes: HashMap::new(),
                 block_registry,
                 render_distance: LOAD_DISTANCE,
+                underwater: false,
 
                 chunk_loading_chan: channel(),
                 threadpool: ThreadPool::new(1),
                 loading_chunks: HashSet::new(),
+                stale_chunks: HashSet::new(),
+                outdated_jobs: HashSet::new(),
 
                 #[cfg(feature = "watchers")]
                 texture_watcher: Watcher::new(&Path::new(env!("CARGO_MANIFEST_DIR")).join("res/textures")),
@@ -221,6 +236,11 @@ impl ChunkRenderer {
         let (_, receiver) = &self.chunk_loading_chan;
         while let Ok((coords, mut chunk)) = receiver.try_recv() {
             self.loading_chunks.remove(&coords);
+
+            if self.outdated_jobs.remove(&coords) {
+                continue;
+            }
+
             chunk.upload_mesh();
             self.meshes.insert(coords, chunk);
         }
@@ -229,16 +249,22 @@ impl ChunkRenderer {
         self.meshes.retain(|coords, _| 
             world.chunks.contains_key(coords)
         );
+        self.stale_chunks
+            .retain(|coords| world.chunks.contains_key(coords));
 
-        // generate missing geometry for loaded chunks
+        // generate missing geometry for loaded chunks, stale meshes are drawn until replaced
         for coords in world.chunks.keys() {
-            if !self.meshes.contains_key(coords) && !self.loading_chunks.contains(coords) {
+            let outdated = !self.meshes.contains_key(coords) || self.stale_chunks.contains(coords);
+
+            if outdated && !self.loading_chunks.contains(coords) {
                 let chunk_group = world.get_chunk_group(*coords);
 
                 if chunk_group.is_none() {
                     continue;
                 }
 
+                self.stale_chunks.remove(coords);
+
                 let (sender, _) = &self.chunk_loading_chan;
                 let tx = sender.clone();
                 let registry = self.block_registry.clone();
@@ -258,6 +284,32 @@ impl ChunkRenderer {
         }
     }
 
+    /// regenerates the mesh of a chunk whose blocks changed
+    pub fn invalidate(&mut self, coords: ChunkGridCoordinate) {
+        self.stale_chunks.insert(coords);
+    }
+
+    /// regenerates the mesh of a chunk right away, for edits that should show on the next frame
+    pub fn remesh(&mut self, world: &World, coords: ChunkGridCoordinate) {
+        let chunk_group = match world.get_chunk_group(coords) {
+            Some(chunk_group) => chunk_group,
+            None => {
+                self.invalidate(coords);
+                return;
+            }
+        };
+
+        let mut mesh = ChunkMeshCollection::generate(&chunk_group, &self.block_registry);
+        mesh.upload_mesh();
+        self.meshes.insert(coords, mesh);
+        self.stale_chunks.remove(&coords);
+
+        // the mesh being generated in the background predates the edit
+        if self.loading_chunks.contains(&coords) {
+            self.outdated_jobs.insert(coords);
+        }
+    }
+
     pub fn draw<C: Camera>(&self, camera: &C) {
         self.program.use_program();
         self.program
@@ -266,9 +318,15 @@ impl ChunkRenderer {
             .set_uniform_texture("diffuse_textures", self.textures.unit());
         self.program
             .set_uniform_v3("camera_position", camera.position());
-        self.program.set_uniform_v3("fog_color", FOG);
+        self.program.set_uniform_v3(
+            "fog_color",
+            if self.underwater { UNDERWATER_FOG } else { FOG },
+        );
         self.program
             .set_uniform_u32("render_distance", self.render_distance as u32);
+        self.program.set_uniform_bool("underwater", self.underwater);
+        self.program
+            .set_uniform_f32("underwater_fog_distance", UNDERWATER_FOG_DISTANCE);
 
         self.textures.bind();
 
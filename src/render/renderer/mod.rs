@@ -1,9 +1,11 @@
 mod chunk_renderer;
+mod outline_renderer;
 mod player_renderer;
 mod renderer;
 mod ui_renderer;
 
-pub use self::chunk_renderer::ChunkRenderer;
+pub use self::chunk_renderer::{ChunkRenderer, UNDERWATER_FOG};
+pub use self::outline_renderer::OutlineRenderer;
 pub use self::player_renderer::PlayerRenderer;
 pub use self::renderer::Renderer;
-pub use self::ui_renderer::UIRenderer;
+pub use self::ui_renderer::{Hud, UIRenderer};
@@ -0,0 +1,113 @@
+use crate::game::BlockCoordinate;
+use crate::render::camera::Camera;
+use crate::render::mesh::Mesh;
+use crate::render::shaders::ShaderProgram;
+
+use math::vector::Vector3;
+
+/// the outline is drawn slightly around the block so it does not fight with its faces
+const OUTLINE_MARGIN: f32 = 0.002;
+const OUTLINE_COLOR: Vector3 = Vector3 {
+    x: 0.0,
+    y: 0.0,
+    z: 0.0,
+};
+const OUTLINE_OPACITY: f32 = 0.6;
+
+/// Wireframe around the block the player targets
+pub struct OutlineRenderer {
+    program: ShaderProgram,
+    mesh: Mesh,
+}
+
+impl OutlineRenderer {
+    pub fn new() -> Self {
+        let vertex_src: &'static str = r#"
+            #version 410 core
+
+            layout (location=0) in vec3 position;
+
+            uniform vec3 block_position;
+            uniform mat4 projection_view; // projection * view
+
+            void main() {
+                gl_Position = projection_view * vec4(block_position + position, 1.0);
+            }
+        "#;
+
+        let fragment_src: &'static str = r#"
+            #version 410 core
+
+            out vec4 color;
+
+            uniform vec3 outline_color;
+            uniform float opacity;
+
+            void main() {
+                color = vec4(outline_color, opacity);
+            }
+        "#;
+
+        match ShaderProgram::new(vertex_src, fragment_src) {
+            Ok(program) => Self {
+                program,
+                mesh: outline_cube(),
+            },
+            Err(err) => {
+                panic!(
+                    "<outline-renderer> could not compile the shader program:\n\n{}\n",
+                    err
+                );
+            }
+        }
+    }
+
+    pub fn draw<C: Camera>(&self, camera: &C, block: BlockCoordinate) {
+        self.program.use_program();
+        self.program
+            .set_uniform_m4("projection_view", camera.projection_view());
+        self.program.set_uniform_v3(
+            "block_position",
+            Vector3 {
+                x: block.x as f32,
+                y: block.y as f32,
+                z: block.z as f32,
+            },
+        );
+        self.program.set_uniform_v3("outline_color", OUTLINE_COLOR);
+        self.program.set_uniform_f32("opacity", OUTLINE_OPACITY);
+
+        unsafe {
+            gl::Enable(gl::DEPTH_TEST);
+            gl::Enable(gl::BLEND);
+        }
+
+        self.mesh.draw_lines();
+
+        unsafe {
+            gl::Disable(gl::BLEND);
+        }
+    }
+}
+
+/// the twelve edges of a unit cube
+fn outline_cube() -> Mesh {
+    let min = -OUTLINE_MARGIN;
+    let max = 1.0 + OUTLINE_MARGIN;
+
+    let vertices = (0..8)
+        .map(|corner| Vector3 {
+            x: if corner & 1 == 0 { min } else { max },
+            y: if corner & 2 == 0 { min } else { max },
+            z: if corner & 4 == 0 { min } else { max },
+        })
+        .collect();
+
+    let indices = vec![
+        0, 1, 2, 3, 4, 5, 6, 7, // along x
+        0, 2, 1, 3, 4, 6, 5, 7, // along y
+        0, 4, 1, 5, 2, 6, 3, 7, // along z
+    ];
+
+    Mesh::new(&vertices, &indices)
+}
@@ -1,14 +1,44 @@
 use crate::game::entity::Player;
-use crate::ops::Drawable;
+use crate::game::EYE_HEIGHT;
+use crate::ops::{Bindable, Drawable};
 use crate::render::camera::Camera;
-use crate::render::mesh::PlayerMesh;
+use crate::render::mesh::{PlayerMesh, TextureQuad};
 use crate::render::shaders::ShaderProgram;
+use crate::render::ui::Font;
 
 use math::vector::Vector3;
+use std::path::Path;
+
+/// forward lean of the body in degrees
+const SNEAKING_LEAN: f32 = 25.0;
+const FLYING_LEAN: f32 = 10.0;
+/// distance between the eyes of a player and the baseline of its name tag
+const NAME_TAG_HEIGHT: f32 = 0.8;
+/// size of a font pixel in world units
+const NAME_TAG_SCALE: f32 = 0.01;
+const NAME_TAG_FONT_SIZE: f32 = 32.0;
+
+const BODY_COLOR: Vector3 = Vector3 {
+    x: 1.0,
+    y: 0.5,
+    z: 0.0,
+};
+const HEAD_COLOR: Vector3 = Vector3 {
+    x: 1.0,
+    y: 0.75,
+    z: 0.5,
+};
+const VISOR_COLOR: Vector3 = Vector3 {
+    x: 0.1,
+    y: 0.1,
+    z: 0.1,
+};
 
 pub struct PlayerRenderer {
     program: ShaderProgram,
     mesh: PlayerMesh,
+    name_tag_program: ShaderProgram,
+    font: Font,
 }
 
 impl PlayerRenderer {
@@ -19,10 +49,25 @@ impl PlayerRenderer {
             layout (location=0) in vec3 position;
 
             uniform vec3 world_position;
+            uniform float yaw;   // radians
+            uniform float pitch; // radians, a positive pitch looks down
             uniform mat4 projection_view; // projection * view
 
+            mat3 rotation_x(float angle) {
+                float c = cos(angle);
+                float s = sin(angle);
+                return mat3(1.0, 0.0, 0.0, 0.0, c, s, 0.0, -s, c);
+            }
+
+            mat3 rotation_y(float angle) {
+                float c = cos(angle);
+                float s = sin(angle);
+                return mat3(c, 0.0, -s, 0.0, 1.0, 0.0, s, 0.0, c);
+            }
+
             void main() {
-                gl_Position = projection_view * vec4(world_position + position, 1.0);
+                vec3 local_position = rotation_y(yaw) * rotation_x(pitch) * position;
+                gl_Position = projection_view * vec4(world_position + local_position, 1.0);
             }
         "#;
 
@@ -31,22 +76,89 @@ impl PlayerRenderer {
 
             out vec4 color;
 
+            uniform vec3 model_color;
+
             void main() {
-                color = vec4(1.0, 0.5, 0.0, 1.0);
+                color = vec4(model_color, 1.0);
             }
         "#;
 
-        match ShaderProgram::new(vertex_src, fragment_src) {
-            Ok(program) => Self {
-                program,
-                mesh: PlayerMesh::new(),
-            },
+        let program = match ShaderProgram::new(vertex_src, fragment_src) {
+            Ok(program) => program,
             Err(err) => {
                 panic!(
                     "<player-renderer> could not compile the shader program:\n\n{}\n",
                     err
                 );
             }
+        };
+
+        Self {
+            program,
+            mesh: PlayerMesh::new(),
+            name_tag_program: Self::name_tag_program(),
+            font: Font::new(
+                Path::new("res/fonts/nunito-regular.ttf"),
+                NAME_TAG_FONT_SIZE,
+            )
+            .unwrap(),
+        }
+    }
+
+    /// glyphs are laid out in font pixels and turned to face the camera
+    fn name_tag_program() -> ShaderProgram {
+        let vertex_src: &'static str = r#"
+            #version 410 core
+
+            layout (location=0) in vec3 position; // font pixels, y pointing down
+            layout (location=1) in vec2 uv_data;
+
+            out vec2 uv;
+
+            uniform vec3 anchor;
+            uniform float scale;
+            uniform mat4 view;
+            uniform mat4 projection_view; // projection * view
+
+            void main() {
+                // the rows of the view rotation are the camera axes in world space
+                vec3 right = vec3(view[0][0], view[1][0], view[2][0]);
+                vec3 up = vec3(view[0][1], view[1][1], view[2][1]);
+                vec3 world_position = anchor + (right * position.x - up * position.y) * scale;
+
+                uv = uv_data;
+                gl_Position = projection_view * vec4(world_position, 1.0);
+            }
+        "#;
+
+        let fragment_src: &'static str = r#"
+            #version 410 core
+
+            in vec2 uv;
+
+            out vec4 color;
+
+            uniform sampler2D diffuse_texture;
+
+            void main() {
+                float alpha = texture(diffuse_texture, uv).r;
+
+                if (alpha < 0.01) {
+                    discard;
+                }
+
+                color = vec4(1.0, 1.0, 1.0, alpha);
+            }
+        "#;
+
+        match ShaderProgram::new(vertex_src, fragment_src) {
+            Ok(program) => program,
+            Err(err) => {
+                panic!(
+                    "<player-renderer> could not compile the name tag shader program:\n\n{}\n",
+                    err
+                );
+            }
         }
     }
 
@@ -61,19 +173,99 @@ impl PlayerRenderer {
 
             for player in players.iter() {
                 // TODO: add camera frustum check
+                let state = player.state();
+                let yaw = state.yaw.to_radians();
+
+                let lean = if state.flags.sneaking {
+                    SNEAKING_LEAN
+                } else if state.flags.flying {
+                    FLYING_LEAN
+                } else {
+                    0.0
+                };
 
                 self.program.set_uniform_v3(
                     "world_position",
-                    player.position()
+                    state.position
                         - Vector3 {
-                            x: 0.5,
-                            y: 1.5,
-                            z: 0.5,
+                            x: 0.0,
+                            y: EYE_HEIGHT,
+                            z: 0.0,
                         },
                 );
+                self.program.set_uniform_f32("yaw", yaw);
+                self.program.set_uniform_f32("pitch", lean.to_radians());
+                self.program.set_uniform_v3("model_color", BODY_COLOR);
+                self.mesh.body.draw();
+
+                self.program
+                    .set_uniform_v3("world_position", state.position);
+                self.program
+                    .set_uniform_f32("pitch", state.pitch.to_radians());
+                self.program.set_uniform_v3("model_color", HEAD_COLOR);
+                self.mesh.head.draw();
+
+                self.program.set_uniform_v3("model_color", VISOR_COLOR);
+                self.mesh.visor.draw();
+            }
+        }
 
-                self.mesh.draw();
+        self.draw_name_tags(camera, players);
+    }
+
+    fn draw_name_tags<C: Camera>(&self, camera: &C, players: &Vec<&Player>) {
+        self.name_tag_program.use_program();
+        self.name_tag_program.set_uniform_m4("view", camera.view());
+        self.name_tag_program
+            .set_uniform_m4("projection_view", camera.projection_view());
+        self.name_tag_program
+            .set_uniform_f32("scale", NAME_TAG_SCALE);
+
+        unsafe {
+            // the glyphs are flipped when going from screen to world space
+            gl::Disable(gl::CULL_FACE);
+            gl::Enable(gl::BLEND);
+            gl::DepthMask(gl::FALSE);
+        }
+
+        for player in players.iter() {
+            let username = match player.username() {
+                Some(username) => String::from(username),
+                None => continue,
+            };
+
+            let width = self
+                .font
+                .iter_for(&username)
+                .last()
+                .map_or(0.0, |(rect, _)| rect.x + rect.width);
+
+            self.name_tag_program.set_uniform_v3(
+                "anchor",
+                player.state().position
+                    + Vector3 {
+                        x: 0.0,
+                        y: NAME_TAG_HEIGHT,
+                        z: 0.0,
+                    },
+            );
+
+            for (rect, texture) in self.font.iter_for(&username) {
+                let quad =
+                    TextureQuad::new_rect(rect.x - width / 2.0, rect.y, rect.width, rect.height);
+
+                self.name_tag_program
+                    .set_uniform_texture("diffuse_texture", texture.unit());
+
+                texture.bind();
+                quad.draw();
             }
         }
+
+        unsafe {
+            gl::DepthMask(gl::TRUE);
+            gl::Disable(gl::BLEND);
+            gl::Enable(gl::CULL_FACE);
+        }
     }
 }
@@ -1,13 +1,15 @@
 use crate::game::entity::Player;
+use crate::game::BlockCoordinate;
 use crate::input::InputHandler;
 use crate::ops::Bindable;
 use crate::render::{
     camera::Camera,
     display::FrameBuffer,
     post::{PostProcessingEffectType, PostProcessingPipeline},
-    renderer::{ChunkRenderer, PlayerRenderer, UIRenderer},
+    renderer::{ChunkRenderer, Hud, OutlineRenderer, PlayerRenderer, UIRenderer, UNDERWATER_FOG},
 };
 
+use core::chunk::ChunkGridCoordinate;
 use core::world::World;
 use math::vector::Vector3;
 
@@ -21,6 +23,7 @@ pub struct Renderer {
     framebuffer: FrameBuffer,
     player_renderer: PlayerRenderer,
     chunk_renderer: ChunkRenderer,
+    outline_renderer: OutlineRenderer,
     ui_renderer: UIRenderer,
     post_pipeline: PostProcessingPipeline,
 }
@@ -28,7 +31,9 @@ pub struct Renderer {
 impl Renderer {
     pub fn new(width: usize, height: usize) -> Self {
         let mut post_pipeline = PostProcessingPipeline::new(width, height);
+        post_pipeline.add(PostProcessingEffectType::Underwater);
         post_pipeline.add(PostProcessingEffectType::FXAA);
+        post_pipeline.set_enabled(PostProcessingEffectType::Underwater, false);
 
         unsafe {
             gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
@@ -38,6 +43,7 @@ impl Renderer {
             framebuffer: FrameBuffer::new(width, height, 1, true),
             player_renderer: PlayerRenderer::new(),
             chunk_renderer: ChunkRenderer::new(),
+            outline_renderer: OutlineRenderer::new(),
             ui_renderer: UIRenderer::new(width, height),
             post_pipeline,
         }
@@ -53,20 +59,55 @@ impl Renderer {
         self.chunk_renderer.update(world, input);
     }
 
-    pub fn draw<C: Camera>(&self, camera: &C, players: &Vec<&Player>) {
+    pub fn invalidate_chunk(&mut self, coords: ChunkGridCoordinate) {
+        self.chunk_renderer.invalidate(coords);
+    }
+
+    /// switches the fog and tints the image while the camera is under water
+    pub fn set_underwater(&mut self, underwater: bool) {
+        self.chunk_renderer.underwater = underwater;
+        self.post_pipeline
+            .set_enabled(PostProcessingEffectType::Underwater, underwater);
+    }
+
+    /// regenerates the mesh of a chunk before the next frame
+    pub fn remesh_chunk(&mut self, world: &World, coords: ChunkGridCoordinate) {
+        self.chunk_renderer.remesh(world, coords);
+    }
+
+    /// `target` is the block the player looks at, it gets outlined
+    pub fn draw<C: Camera>(
+        &self,
+        camera: &C,
+        players: &Vec<&Player>,
+        target: Option<BlockCoordinate>,
+        hud: &Hud,
+    ) {
         self.framebuffer.bind();
 
+        // the fog hides the sky under water
+        let background = if self.chunk_renderer.underwater {
+            UNDERWATER_FOG
+        } else {
+            SKY_COLOR
+        };
+
         unsafe {
-            gl::ClearColor(SKY_COLOR.x, SKY_COLOR.y, SKY_COLOR.z, 1.0);
+            gl::ClearColor(background.x, background.y, background.z, 1.0);
         }
 
         self.framebuffer.clear(true, true, false);
 
         self.chunk_renderer.draw(camera);
+
+        if let Some(block) = target {
+            self.outline_renderer.draw(camera, block);
+        }
+
         self.player_renderer.draw(camera, players);
 
         self.post_pipeline.apply(&self.framebuffer);
 
-        self.ui_renderer.draw();
+        self.ui_renderer.draw(hud);
     }
 }
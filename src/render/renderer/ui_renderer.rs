@@ -1,3 +1,5 @@
+use crate::game::Chat;
+use crate::network::PlayerListEntry;
 use crate::ops::{Bindable, Drawable};
 use crate::render::{
     camera::OrthographicProjection, mesh::TextureQuad, shaders::ShaderProgram, ui::Font,
@@ -5,11 +7,41 @@ use crate::render::{
 
 use math::vector::Vector3;
 use std::path::Path;
+use std::time::Instant;
+
+const FONT_SIZE: f32 = 32.0;
+const MARGIN: f32 = 20.0;
+const LINE_HEIGHT: f32 = FONT_SIZE * 1.25;
+const TEXT_COLOR: Vector3 = Vector3 {
+    x: 1.0,
+    y: 1.0,
+    z: 1.0,
+};
+const CHAT_BACKGROUND_COLOR: Vector3 = Vector3 {
+    x: 0.0,
+    y: 0.0,
+    z: 0.0,
+};
+const CHAT_BACKGROUND_OPACITY: f32 = 0.5;
+const PLAYER_LIST_WIDTH: f32 = 600.0;
+
+/// Everything drawn over the world
+pub struct Hud<'a> {
+    /// message describing the connection when it is not usable
+    pub status: Option<&'a str>,
+    pub chat: &'a Chat,
+    /// shown while tab is held
+    pub player_list: Option<&'a [PlayerListEntry]>,
+    /// lines of the debug overlay when it is open
+    pub debug: Option<&'a [String]>,
+}
 
 pub struct UIRenderer {
     program: ShaderProgram,
     projection: OrthographicProjection,
     font: Font,
+    width: f32,
+    height: f32,
 }
 
 impl UIRenderer {
@@ -41,16 +73,17 @@ impl UIRenderer {
             uniform vec3 background_color;
             uniform vec3 tint_color;
             uniform bool render_texture;
+            uniform float opacity;
 
             void main() {
                 if (!render_texture) {
-                    color = vec4(background_color, 1.0);
+                    color = vec4(background_color, opacity);
                 } else {
                     color = texture(diffuse_texture, uv);
                     color = vec4(color.r * tint_color.r, 
                                  color.r * tint_color.g, 
                                  color.r * tint_color.b, 
-                                 color.r * 1.0);
+                                 color.r * opacity);
                 }
 
                 if (color.a < 0.01) {
@@ -62,7 +95,7 @@ impl UIRenderer {
         match ShaderProgram::new(vertex_src, fragment_src) {
             Ok(program) => Self {
                 program,
-                font: Font::new(Path::new("res/fonts/nunito-regular.ttf"), 128.0).unwrap(),
+                font: Font::new(Path::new("res/fonts/nunito-regular.ttf"), FONT_SIZE).unwrap(),
                 projection: OrthographicProjection::new(
                     0.0,
                     width as f32,
@@ -71,6 +104,8 @@ impl UIRenderer {
                     -1.0,
                     1.0,
                 ),
+                width: width as f32,
+                height: height as f32,
             },
             Err(err) => {
                 error!("could not compile shader program {}:{}", file!(), line!());
@@ -81,9 +116,11 @@ impl UIRenderer {
 
     pub fn resize(&mut self, width: usize, height: usize) {
         self.projection.resize(width, height);
+        self.width = width as f32;
+        self.height = height as f32;
     }
 
-    pub fn draw(&self) {
+    pub fn draw(&self, hud: &Hud) {
         self.program.use_program();
         self.program
             .set_uniform_m4("projection", self.projection.matrix());
@@ -93,29 +130,129 @@ impl UIRenderer {
             gl::Enable(gl::BLEND);
         }
 
-        for (rect, texture) in self.font.iter_for(&String::from("Hello World!")) {
-            let quad =
-                TextureQuad::new_rect(rect.x + 300.0, rect.y + 300.0, rect.width, rect.height);
+        if let Some(status) = hud.status {
+            self.draw_text(status, MARGIN, MARGIN + FONT_SIZE, TEXT_COLOR, 1.0);
+        }
 
-            self.program.set_uniform_bool("render_texture", true);
-            self.program
-                .set_uniform_texture("diffuse_texture", texture.unit());
+        self.draw_chat(hud.chat);
 
-            self.program.set_uniform_v3(
-                "tint_color",
-                Vector3 {
-                    x: 0.0,
-                    y: 0.0,
-                    z: 0.0,
-                },
-            );
+        if let Some(entries) = hud.player_list {
+            self.draw_player_list(entries);
+        }
 
-            texture.bind();
-            // quad.draw();
+        if let Some(lines) = hud.debug {
+            self.draw_debug(lines);
         }
 
         unsafe {
             gl::Disable(gl::BLEND);
         }
     }
+
+    /// history in the bottom left corner, above the input box when it is open
+    fn draw_chat(&self, chat: &Chat) {
+        let input_baseline = self.height - MARGIN;
+
+        if let Some(input) = chat.input() {
+            let top = input_baseline - FONT_SIZE;
+            self.draw_rect(
+                MARGIN / 2.0,
+                top,
+                self.width - MARGIN,
+                LINE_HEIGHT,
+                CHAT_BACKGROUND_COLOR,
+                CHAT_BACKGROUND_OPACITY,
+            );
+
+            let text = format!("{}_", input);
+            self.draw_text(&text, MARGIN, input_baseline, TEXT_COLOR, 1.0);
+        }
+
+        let lines = chat.visible_lines(Instant::now());
+        let mut baseline = input_baseline - LINE_HEIGHT * lines.len() as f32;
+
+        for (text, opacity) in lines {
+            self.draw_text(text, MARGIN, baseline, TEXT_COLOR, opacity);
+            baseline += LINE_HEIGHT;
+        }
+
+        // tells there are newer messages below
+        if chat.is_scrolled() {
+            let marker = "...";
+            let x = self.width - MARGIN - self.text_width(marker);
+            self.draw_text(marker, x, input_baseline - LINE_HEIGHT, TEXT_COLOR, 1.0);
+        }
+    }
+
+    /// usernames and pings in a panel centered at the top of the screen
+    fn draw_player_list(&self, entries: &[PlayerListEntry]) {
+        let left = (self.width - PLAYER_LIST_WIDTH) / 2.0;
+        let right = left + PLAYER_LIST_WIDTH;
+        let top = MARGIN * 2.0 + LINE_HEIGHT;
+
+        self.draw_rect(
+            left,
+            top,
+            PLAYER_LIST_WIDTH,
+            LINE_HEIGHT * entries.len() as f32 + MARGIN,
+            CHAT_BACKGROUND_COLOR,
+            CHAT_BACKGROUND_OPACITY,
+        );
+
+        let mut baseline = top + MARGIN / 2.0 + FONT_SIZE;
+
+        for entry in entries {
+            let ping = format!("{} ms", entry.ping);
+            let ping_x = right - MARGIN - self.text_width(&ping);
+
+            self.draw_text(&entry.username, left + MARGIN, baseline, TEXT_COLOR, 1.0);
+            self.draw_text(&ping, ping_x, baseline, TEXT_COLOR, 1.0);
+
+            baseline += LINE_HEIGHT;
+        }
+    }
+
+    /// right aligned in the top right corner
+    fn draw_debug(&self, lines: &[String]) {
+        let mut baseline = MARGIN + FONT_SIZE;
+
+        for line in lines {
+            let x = self.width - MARGIN - self.text_width(line);
+            self.draw_text(line, x, baseline, TEXT_COLOR, 1.0);
+
+            baseline += LINE_HEIGHT;
+        }
+    }
+
+    fn text_width(&self, text: &str) -> f32 {
+        self.font
+            .iter_for(&String::from(text))
+            .last()
+            .map_or(0.0, |(rect, _)| rect.x + rect.width)
+    }
+
+    fn draw_rect(&self, x: f32, y: f32, width: f32, height: f32, color: Vector3, opacity: f32) {
+        self.program.set_uniform_bool("render_texture", false);
+        self.program.set_uniform_v3("background_color", color);
+        self.program.set_uniform_f32("opacity", opacity);
+
+        TextureQuad::new_rect(x, y, width, height).draw();
+    }
+
+    /// draws a single line of text, `y` being the baseline
+    fn draw_text(&self, text: &str, x: f32, y: f32, color: Vector3, opacity: f32) {
+        self.program.set_uniform_bool("render_texture", true);
+        self.program.set_uniform_v3("tint_color", color);
+        self.program.set_uniform_f32("opacity", opacity);
+
+        for (rect, texture) in self.font.iter_for(&String::from(text)) {
+            let quad = TextureQuad::new_rect(rect.x + x, rect.y + y, rect.width, rect.height);
+
+            self.program
+                .set_uniform_texture("diffuse_texture", texture.unit());
+
+            texture.bind();
+            quad.draw();
+        }
+    }
 }
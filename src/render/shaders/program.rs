@@ -58,6 +58,10 @@ impl ShaderProgram {
         unsafe { gl::Uniform1ui(self.get_uniform_location(name), value) }
     }
 
+    pub fn set_uniform_f32(&self, name: &str, value: f32) {
+        unsafe { gl::Uniform1f(self.get_uniform_location(name), value) }
+    }
+
     pub fn set_uniform_v2(&self, name: &str, value: Vector2) {
         unsafe { gl::Uniform2f(self.get_uniform_location(name), value.x, value.y) }
     }
@@ -0,0 +1,99 @@
+use crate::network::{Channel, Packet, PublicKey, Session, SessionCipher};
+
+use std::time::{Duration, Instant};
+
+/// a heartbeat is sent when nothing else was sent for this long
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(1);
+/// the client is considered gone when nothing was received for this long
+const CONNECTION_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Keys agreed on during the handshake of an encrypted connection
+pub struct Encryption {
+    pub client_key: PublicKey,
+    pub server_key: PublicKey,
+    pub cipher: SessionCipher,
+}
+
+/// Server side of the connection with one client
+pub struct Connection {
+    pub session: Session,
+    pub channel: Channel,
+    encryption: Option<Encryption>,
+    last_received: Instant,
+    last_sent: Instant,
+}
+
+impl Connection {
+    pub fn new(session: Session, encryption: Option<Encryption>, now: Instant) -> Self {
+        Self {
+            session,
+            channel: Channel::new(),
+            encryption,
+            last_received: now,
+            last_sent: now,
+        }
+    }
+
+    pub fn is_encrypted(&self) -> bool {
+        self.encryption.is_some()
+    }
+
+    /// key the client offered in the handshake that opened this connection
+    pub fn client_key(&self) -> Option<PublicKey> {
+        self.encryption
+            .as_ref()
+            .map(|encryption| encryption.client_key)
+    }
+
+    pub fn server_key(&self) -> Option<PublicKey> {
+        self.encryption
+            .as_ref()
+            .map(|encryption| encryption.server_key)
+    }
+
+    /// forgets the messages in flight, both ends start over from the first sequence
+    pub fn reset_channel(&mut self) {
+        self.channel = Channel::new();
+    }
+
+    pub fn received(&mut self, now: Instant) {
+        self.last_received = now;
+    }
+
+    pub fn timed_out(&self, now: Instant) -> bool {
+        now.duration_since(self.last_received) >= CONNECTION_TIMEOUT
+    }
+
+    /// encrypts the packet for an encrypted connection, none when that failed
+    pub fn seal(&mut self, packet: Packet) -> Option<Packet> {
+        match self.encryption.as_mut() {
+            Some(encryption) => encryption.cipher.seal(&packet),
+            None => Some(packet),
+        }
+    }
+
+    /// none when the connection is not encrypted or the packet is not authentic
+    pub fn open(&mut self, counter: u64, data: &[u8]) -> Option<Packet> {
+        self.encryption
+            .as_mut()
+            .and_then(|encryption| encryption.cipher.open(counter, data))
+    }
+
+    /// packets to send right now, a heartbeat when the client did not hear from us for a while
+    pub fn poll(&mut self, now: Instant) -> Vec<Packet> {
+        let mut packets = self.channel.poll(now);
+
+        if packets.is_empty() && now.duration_since(self.last_sent) >= HEARTBEAT_INTERVAL {
+            packets.push(Packet::Heartbeat);
+        }
+
+        if !packets.is_empty() {
+            self.last_sent = now;
+        }
+
+        packets
+            .into_iter()
+            .filter_map(|packet| self.seal(packet))
+            .collect()
+    }
+}
@@ -0,0 +1,154 @@
+use crate::network::{MemoryTransport, Packet, TcpTransport, Transport};
+
+use std::collections::HashMap;
+use std::io;
+use std::net::{SocketAddr, TcpListener, UdpSocket};
+
+const MAX_DATAGRAM_SIZE: usize = 65535;
+/// streams kept open at the same time, the ones beyond are closed right away
+const MAX_TCP_STREAMS: usize = 32;
+
+/// Where a client is connected from
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Peer {
+    /// the client running in the same process
+    Local,
+    Lan(SocketAddr),
+    /// a client on a network that blocks udp, its datagrams are framed over a stream
+    Tcp(SocketAddr),
+}
+
+/// Every way the server can be reached, the in process client and the lan once opened, over udp
+/// or tcp on the same port
+pub struct Endpoints {
+    local: MemoryTransport,
+    local_closed: bool,
+    lan: Option<UdpSocket>,
+    listener: Option<TcpListener>,
+    streams: HashMap<SocketAddr, TcpTransport>,
+}
+
+impl Endpoints {
+    pub fn new(local: MemoryTransport) -> Self {
+        Self {
+            local,
+            local_closed: false,
+            lan: None,
+            listener: None,
+            streams: HashMap::new(),
+        }
+    }
+
+    pub fn open_lan(&mut self, port: u16) -> io::Result<SocketAddr> {
+        let socket = UdpSocket::bind(("0.0.0.0", port))?;
+        socket.set_nonblocking(true)?;
+
+        let address = socket.local_addr()?;
+        let listener = TcpListener::bind(("0.0.0.0", address.port()))?;
+        listener.set_nonblocking(true)?;
+
+        self.lan = Some(socket);
+        self.listener = Some(listener);
+
+        Ok(address)
+    }
+
+    /// forgets the stream of a client whose connection is over
+    pub fn close(&mut self, peer: Peer) {
+        if let Peer::Tcp(address) = peer {
+            self.streams.remove(&address);
+        }
+    }
+
+    pub fn is_lan_open(&self) -> bool {
+        self.lan.is_some()
+    }
+
+    /// the local client went away, which ends the game
+    pub fn is_local_closed(&self) -> bool {
+        self.local_closed
+    }
+
+    /// datagrams received since the last call
+    pub fn receive(&mut self) -> Vec<(Peer, Vec<u8>)> {
+        let mut datagrams = Vec::new();
+
+        while !self.local_closed {
+            match self.local.receive() {
+                Ok(Some(data)) => datagrams.push((Peer::Local, data)),
+                Ok(None) => break,
+                Err(_) => self.local_closed = true,
+            }
+        }
+
+        if let Some(socket) = self.lan.as_ref() {
+            let mut data = [0; MAX_DATAGRAM_SIZE];
+
+            while let Ok((size, address)) = socket.recv_from(&mut data) {
+                datagrams.push((Peer::Lan(address), data[..size].to_vec()));
+            }
+        }
+
+        if let Some(listener) = self.listener.as_ref() {
+            while let Ok((stream, address)) = listener.accept() {
+                if self.streams.len() >= MAX_TCP_STREAMS {
+                    warn!("<server> too many tcp clients, closing {}", address);
+                    continue;
+                }
+
+                match TcpTransport::new(stream) {
+                    Ok(transport) => {
+                        self.streams.insert(address, transport);
+                    }
+                    Err(err) => warn!(
+                        "<server> could not set up the stream of {}: {}",
+                        address, err
+                    ),
+                }
+            }
+        }
+
+        self.streams.retain(|address, stream| loop {
+            match stream.receive() {
+                Ok(Some(data)) => datagrams.push((Peer::Tcp(*address), data)),
+                Ok(None) => break true,
+                Err(_) => break false,
+            }
+        });
+
+        datagrams
+    }
+
+    pub fn send(&mut self, peer: Peer, packet: &Packet) {
+        let buffer = match bincode::serialize(packet) {
+            Ok(buffer) => buffer,
+            Err(err) => {
+                error!("<server> could not serialize packet: {}", err);
+                return;
+            }
+        };
+
+        match peer {
+            Peer::Local => {
+                if self.local.send(&buffer).is_err() {
+                    self.local_closed = true;
+                }
+            }
+            Peer::Lan(address) => {
+                if let Some(socket) = self.lan.as_ref() {
+                    let _ = socket.send_to(&buffer, address);
+                }
+            }
+            Peer::Tcp(address) => {
+                let failed = self
+                    .streams
+                    .get_mut(&address)
+                    .map_or(false, |stream| stream.send(&buffer).is_err());
+
+                if failed {
+                    self.streams.remove(&address);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,81 @@
+use crate::network::MemoryTransport;
+use crate::server::server::Server;
+
+use core::utils::sleep;
+use std::io;
+use std::sync::mpsc::{self, Sender, TryRecvError};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// time between two updates of the server, keeps the added latency low for the local player
+const UPDATE_INTERVAL: Duration = Duration::from_millis(5);
+
+enum Command {
+    OpenToLan,
+    Stop,
+}
+
+/// Server running on a background thread for singleplayer, the local client talks to it through
+/// an in memory transport
+pub struct IntegratedServer {
+    commands: Sender<Command>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl IntegratedServer {
+    /// returns the handle of the server and the transport the local client connects with
+    pub fn start() -> io::Result<(Self, MemoryTransport)> {
+        let (client_transport, server_transport) = MemoryTransport::pair();
+        let (commands, receiver) = mpsc::channel();
+
+        let thread = thread::Builder::new()
+            .name(String::from("integrated-server"))
+            .spawn(move || {
+                let mut server = Server::new(server_transport);
+
+                info!("<server> integrated server started");
+
+                while server.is_running() {
+                    let start = Instant::now();
+
+                    match receiver.try_recv() {
+                        Ok(Command::OpenToLan) => server.open_to_lan(),
+                        Ok(Command::Stop) | Err(TryRecvError::Disconnected) => break,
+                        Err(TryRecvError::Empty) => {}
+                    }
+
+                    server.update(start);
+
+                    if let Some(cooldown) = UPDATE_INTERVAL.checked_sub(start.elapsed()) {
+                        sleep(cooldown);
+                    }
+                }
+
+                info!("<server> integrated server stopped");
+            })?;
+
+        Ok((
+            Self {
+                commands,
+                thread: Some(thread),
+            },
+            client_transport,
+        ))
+    }
+
+    pub fn open_to_lan(&self) {
+        let _ = self.commands.send(Command::OpenToLan);
+    }
+}
+
+impl Drop for IntegratedServer {
+    fn drop(&mut self) {
+        let _ = self.commands.send(Command::Stop);
+
+        if let Some(thread) = self.thread.take() {
+            if thread.join().is_err() {
+                error!("<server> the integrated server crashed");
+            }
+        }
+    }
+}
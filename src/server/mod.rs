@@ -0,0 +1,6 @@
+mod connection;
+mod endpoint;
+mod integrated_server;
+mod server;
+
+pub use self::integrated_server::IntegratedServer;
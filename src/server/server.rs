@@ -0,0 +1,1138 @@
+use crate::game::entity::{is_valid_username, PlayerId, PlayerState};
+use crate::game::{
+    block_at, chunk_coordinate, chunk_distance, chunks_in_range, is_out_of_range,
+    load_block_registry, material_at, set_block, BlockCoordinate, Body, ChunkData, InputSequence,
+    MovementFlags, MovementInput, AIR, MAX_MESSAGE_LENGTH, SPAWN_POSITION,
+};
+use crate::network::{
+    dequantize_angle, verify_resume_proof, ClientMessage, Delivery, DiscoveryResponder,
+    EncodedSnapshot, KeyExchange, MemoryTransport, Packet, PlayerListEntry, PublicKey,
+    QuantizedPosition, QuantizedState, RejectReason, Resume, Role, ServerDescription,
+    ServerMessage, ServerTime, Session, SnapshotHistory, DEFAULT_PORT, DISCOVERY_PORT,
+    PROTOCOL_VERSION,
+};
+use crate::server::connection::{Connection, Encryption};
+use crate::server::endpoint::{Endpoints, Peer};
+use crate::utils::Random;
+
+use core::block::BlockRegistry;
+use core::chunk::ChunkGridCoordinate;
+use core::events::{ClientEvent, ServerEvent};
+use core::world::World;
+use std::collections::{HashMap, HashSet};
+use std::net::{Ipv4Addr, SocketAddr};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// time between two snapshots of the players
+const SNAPSHOT_INTERVAL: Duration = Duration::from_millis(50);
+/// time between two player lists, which refresh the pings
+const PLAYER_LIST_INTERVAL: Duration = Duration::from_secs(1);
+/// a player whose connection was lost can resume its session for this long
+const SESSION_TIMEOUT: Duration = Duration::from_secs(60);
+const MAX_PLAYERS: usize = 8;
+const SERVER_NAME: &'static str = "Integrated server";
+/// distance from the eyes of a player to the center of the blocks it can edit
+const MAX_REACH: f32 = 8.0;
+/// chunks sent to a player per snapshot interval, the closest ones first
+const CHUNKS_PER_UPDATE: usize = 4;
+/// longest input applied, the client makes one per tick
+const MAX_INPUT_DURATION: Duration = Duration::from_millis(100);
+/// time a client can spend in inputs ahead of the time that passed, for the inputs the network
+/// delays and sends at once
+const MAX_INPUT_BUDGET: Duration = Duration::from_secs(1);
+
+struct ServerPlayer {
+    token: u64,
+    username: String,
+    state: PlayerState,
+    /// moved by the inputs of the client, its position is the one of the state
+    body: Body,
+    last_input: Option<InputSequence>,
+    /// time the inputs can still move the player, refilled by the time that passes so a client
+    /// cannot move faster by sending more or longer inputs
+    input_budget: Duration,
+    budget_refilled: Instant,
+    /// flying goes through blocks, only the player the game runs for is allowed to
+    can_fly: bool,
+    /// the client asked to join the game, before that it is only connected
+    joined: bool,
+    /// when the connection was lost, the player is kept around to resume its session
+    lost_at: Option<Instant>,
+    /// chunks the client has, forgotten as the player walks away from them like the client does
+    sent_chunks: HashSet<ChunkGridCoordinate>,
+    /// snapshots sent to the client, the next one is encoded against the latest it acknowledged
+    snapshots: SnapshotHistory,
+    acked_snapshot: Option<ServerTime>,
+    /// the session was opened by an encrypted connection, only another one can resume it
+    encrypted: bool,
+}
+
+/// Authoritative game state shared by every client, speaks the same protocol as a dedicated
+/// server
+pub struct Server {
+    world: World,
+    block_registry: BlockRegistry,
+    start: Instant,
+    random: Random,
+    endpoints: Endpoints,
+    responder: Option<DiscoveryResponder>,
+    connections: HashMap<Peer, Connection>,
+    players: HashMap<PlayerId, ServerPlayer>,
+    next_player_id: PlayerId,
+    last_snapshot: Instant,
+    last_player_list: Instant,
+}
+
+impl Server {
+    pub fn new(local: MemoryTransport) -> Self {
+        let now = Instant::now();
+        let seed = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |time| time.as_nanos() as u64);
+
+        Self {
+            world: World::new(),
+            block_registry: load_block_registry(),
+            start: now,
+            random: Random::new(seed),
+            endpoints: Endpoints::new(local),
+            responder: None,
+            connections: HashMap::new(),
+            players: HashMap::new(),
+            next_player_id: 1,
+            last_snapshot: now,
+            last_player_list: now,
+        }
+    }
+
+    /// the server stops with the game of the local client
+    pub fn is_running(&self) -> bool {
+        !self.endpoints.is_local_closed()
+    }
+
+    /// lets the other clients of the local network find and join the game
+    pub fn open_to_lan(&mut self) {
+        if self.endpoints.is_lan_open() {
+            return;
+        }
+
+        let address = match self.endpoints.open_lan(DEFAULT_PORT) {
+            Ok(address) => address,
+            Err(err) => {
+                error!("<server> could not open to lan: {}", err);
+                return;
+            }
+        };
+
+        let description = ServerDescription {
+            name: String::from(SERVER_NAME),
+            players: self.joined_players().len() as u32,
+            max_players: MAX_PLAYERS as u32,
+            protocol_version: PROTOCOL_VERSION,
+            port: address.port(),
+        };
+
+        match DiscoveryResponder::bind(
+            SocketAddr::from((Ipv4Addr::UNSPECIFIED, DISCOVERY_PORT)),
+            description,
+        ) {
+            Ok(responder) => self.responder = Some(responder),
+            Err(err) => warn!("<server> the game cannot be discovered: {}", err),
+        }
+
+        info!("<server> open to lan on port {}", address.port());
+    }
+
+    pub fn update(&mut self, now: Instant) {
+        for (peer, data) in self.endpoints.receive() {
+            match bincode::deserialize(&data) {
+                Ok(packet) => self.handle_packet(peer, packet, now),
+                Err(err) => warn!("<server> invalid packet from {:?}: {}", peer, err),
+            }
+        }
+
+        self.expire(now);
+
+        if now.duration_since(self.last_snapshot) >= SNAPSHOT_INTERVAL {
+            self.send_snapshots();
+            self.stream_chunks();
+            self.last_snapshot = now;
+        }
+
+        if now.duration_since(self.last_player_list) >= PLAYER_LIST_INTERVAL {
+            self.send_player_list();
+            self.last_player_list = now;
+        }
+
+        for (peer, connection) in self.connections.iter_mut() {
+            for packet in connection.poll(now) {
+                self.endpoints.send(*peer, &packet);
+            }
+        }
+
+        if let Some(responder) = self.responder.as_mut() {
+            responder.description.players =
+                self.players.values().filter(|player| player.joined).count() as u32;
+            responder.poll();
+        }
+    }
+
+    fn time(&self) -> ServerTime {
+        self.start.elapsed().as_millis() as ServerTime
+    }
+
+    fn joined_players(&self) -> Vec<PlayerId> {
+        self.players
+            .iter()
+            .filter(|(_, player)| player.joined)
+            .map(|(id, _)| *id)
+            .collect()
+    }
+
+    fn handle_packet(&mut self, peer: Peer, packet: Packet, now: Instant) {
+        let packet = match packet {
+            Packet::Connect {
+                protocol_version,
+                resume,
+                key,
+            } => {
+                self.handshake(peer, protocol_version, resume, key, now);
+                return;
+            }
+            Packet::Sealed { counter, data } => {
+                // forged, replayed or from a peer without an encrypted connection
+                match self
+                    .connections
+                    .get_mut(&peer)
+                    .and_then(|connection| connection.open(counter, &data))
+                {
+                    Some(packet) => packet,
+                    None => return,
+                }
+            }
+            packet => {
+                // anyone can send plain packets from the address of an encrypted connection
+                let encrypted = self
+                    .connections
+                    .get(&peer)
+                    .map_or(false, |connection| connection.is_encrypted());
+
+                if encrypted {
+                    return;
+                }
+
+                packet
+            }
+        };
+
+        if let Some(connection) = self.connections.get_mut(&peer) {
+            connection.received(now);
+        }
+
+        match packet {
+            Packet::TimeRequest { client_time } => {
+                if self.connections.contains_key(&peer) {
+                    let receive_time = now.duration_since(self.start).as_millis() as ServerTime;
+                    let response = Packet::TimeResponse {
+                        client_time,
+                        receive_time,
+                        send_time: self.time(),
+                    };
+                    self.send_packet(peer, response);
+                }
+            }
+            Packet::Connect { .. }
+            | Packet::ServerKey { .. }
+            | Packet::Accept { .. }
+            | Packet::Reject { .. }
+            | Packet::Heartbeat
+            | Packet::TimeResponse { .. }
+            | Packet::Sealed { .. } => {}
+            packet => {
+                let (player_id, payloads) = match self.connections.get_mut(&peer) {
+                    Some(connection) => (
+                        connection.session.player_id,
+                        connection.channel.receive(packet, now),
+                    ),
+                    None => return,
+                };
+
+                for payload in payloads {
+                    match bincode::deserialize(&payload) {
+                        Ok(message) => self.handle_message(player_id, message, now),
+                        Err(err) => warn!("<server> invalid message from {}: {}", player_id, err),
+                    }
+                }
+            }
+        }
+    }
+
+    fn handshake(
+        &mut self,
+        peer: Peer,
+        protocol_version: u32,
+        resume: Option<Resume>,
+        key: Option<PublicKey>,
+        now: Instant,
+    ) {
+        if protocol_version != PROTOCOL_VERSION {
+            let reason = RejectReason::VersionMismatch {
+                server: PROTOCOL_VERSION,
+            };
+            self.endpoints.send(peer, &Packet::Reject { reason });
+            return;
+        }
+
+        if let Some(connection) = self.connections.get_mut(&peer) {
+            match (connection.client_key(), key) {
+                // the accept got lost, the client is still asking with the same key
+                (Some(current), Some(key)) if current == key => {
+                    self.accept(peer);
+                    return;
+                }
+                // the client starts over with a new channel after a reconnect, so does the server
+                (None, None) => {
+                    connection.reset_channel();
+                    self.accept(peer);
+                    return;
+                }
+                _ => {}
+            }
+        }
+
+        let resumable = match self.resumable_session(resume, key) {
+            Ok(session) => session,
+            Err(reason) => {
+                warn!("<server> refused a handshake from {:?}: {}", peer, reason);
+                self.endpoints.send(peer, &Packet::Reject { reason });
+                return;
+            }
+        };
+
+        // an encrypted connection is only replaced by a client that proves it owns the session
+        if let Some(connection) = self.connections.get(&peer) {
+            let owner = resumable.map(|session| session.player_id);
+
+            if connection.is_encrypted() && owner != Some(connection.session.player_id) {
+                warn!(
+                    "<server> ignored a handshake over the connection of {:?}",
+                    peer
+                );
+                return;
+            }
+        }
+
+        let encryption = match key {
+            Some(client_key) => {
+                let exchange = KeyExchange::new();
+
+                match exchange.complete(&client_key, Role::Server) {
+                    Some(cipher) => Some(Encryption {
+                        client_key,
+                        server_key: exchange.public_key(),
+                        cipher,
+                    }),
+                    None => {
+                        warn!("<server> invalid key from {:?}", peer);
+                        self.endpoints.send(
+                            peer,
+                            &Packet::Reject {
+                                reason: RejectReason::HandshakeFailed,
+                            },
+                        );
+                        return;
+                    }
+                }
+            }
+            None => None,
+        };
+
+        let session = match resumable {
+            Some(session) => {
+                // the previous connection might not have timed out yet
+                self.connections
+                    .retain(|_, connection| connection.session != session);
+                let player = self.players.get_mut(&session.player_id).unwrap();
+                player.lost_at = None;
+                player.encrypted |= key.is_some();
+
+                info!("<server> player {} resumed its session", session.player_id);
+                session
+            }
+            None => {
+                if self.players.len() >= MAX_PLAYERS {
+                    self.endpoints.send(
+                        peer,
+                        &Packet::Reject {
+                            reason: RejectReason::ServerFull,
+                        },
+                    );
+                    return;
+                }
+
+                let session = Session {
+                    player_id: self.next_player_id,
+                    token: self.random.next_u64(),
+                };
+                self.next_player_id += 1;
+
+                self.players.insert(
+                    session.player_id,
+                    ServerPlayer {
+                        token: session.token,
+                        username: default_username(session.player_id),
+                        state: PlayerState::new(SPAWN_POSITION),
+                        body: Body::new(SPAWN_POSITION),
+                        last_input: None,
+                        input_budget: Duration::from_secs(0),
+                        budget_refilled: now,
+                        can_fly: peer == Peer::Local,
+                        joined: false,
+                        lost_at: None,
+                        sent_chunks: HashSet::new(),
+                        snapshots: SnapshotHistory::new(),
+                        acked_snapshot: None,
+                        encrypted: key.is_some(),
+                    },
+                );
+
+                info!(
+                    "<server> player {} connected from {:?}",
+                    session.player_id, peer
+                );
+                session
+            }
+        };
+
+        // the session of a plain connection replaced by a new one from the same address
+        if let Some(previous) = self.connections.remove(&peer) {
+            if let Some(player) = self.players.get_mut(&previous.session.player_id) {
+                player.lost_at = Some(now);
+            }
+        }
+
+        self.connections
+            .insert(peer, Connection::new(session, encryption, now));
+        self.accept(peer);
+    }
+
+    /// the session the client asks to resume, if it owns it
+    fn resumable_session(
+        &self,
+        resume: Option<Resume>,
+        key: Option<PublicKey>,
+    ) -> Result<Option<Session>, RejectReason> {
+        let (player_id, valid) = match (resume, key) {
+            (Some(Resume::Token(session)), _) => (
+                session.player_id,
+                self.players
+                    .get(&session.player_id)
+                    .map_or(false, |player| player.token == session.token),
+            ),
+            (Some(Resume::Proof { player_id, proof }), Some(key)) => (
+                player_id,
+                self.players.get(&player_id).map_or(false, |player| {
+                    verify_resume_proof(player.token, &key, &proof)
+                }),
+            ),
+            _ => return Ok(None),
+        };
+
+        let player = match self.players.get(&player_id) {
+            Some(player) if valid => player,
+            _ => return Ok(None),
+        };
+
+        // the token of an encrypted session is not trusted once it traveled in clear
+        if player.encrypted && key.is_none() {
+            return Err(RejectReason::EncryptionRequired);
+        }
+
+        Ok(Some(Session {
+            player_id,
+            token: player.token,
+        }))
+    }
+
+    /// the key of an encrypted connection goes in clear, the accept that follows is sealed
+    fn accept(&mut self, peer: Peer) {
+        let (session, server_key) = match self.connections.get(&peer) {
+            Some(connection) => (connection.session, connection.server_key()),
+            None => return,
+        };
+
+        if let Some(key) = server_key {
+            self.endpoints.send(peer, &Packet::ServerKey { key });
+        }
+
+        self.send_packet(
+            peer,
+            Packet::Accept {
+                protocol_version: PROTOCOL_VERSION,
+                session,
+            },
+        );
+    }
+
+    /// sends a packet outside of the channel, sealed for an encrypted connection
+    fn send_packet(&mut self, peer: Peer, packet: Packet) {
+        let packet = match self.connections.get_mut(&peer) {
+            Some(connection) => match connection.seal(packet) {
+                Some(packet) => packet,
+                None => {
+                    error!("<server> could not seal a packet for {:?}", peer);
+                    return;
+                }
+            },
+            None => packet,
+        };
+
+        self.endpoints.send(peer, &packet);
+    }
+
+    fn handle_message(&mut self, id: PlayerId, message: ClientMessage, now: Instant) {
+        match message {
+            ClientMessage::Event(ClientEvent::PlayerConnect) => self.join(id, None),
+            ClientMessage::Join { username } => self.join(id, Some(username)),
+            ClientMessage::Event(ClientEvent::PlayerDisconnect) => {
+                info!("<server> player {} left", id);
+                self.remove_player(id);
+            }
+            // the position of a player only comes from its inputs, it cannot be set directly
+            ClientMessage::Event(ClientEvent::PlayerMove { .. }) => {
+                warn!("<server> ignored a position sent by player {}", id);
+            }
+            ClientMessage::PlayerUpdate {
+                inputs,
+                look,
+                snapshot_ack,
+            } => {
+                let player = match self.players.get_mut(&id) {
+                    Some(player) => player,
+                    None => return,
+                };
+
+                let world = &self.world;
+                let registry = &self.block_registry;
+                let material = |position| material_at(world, registry, position);
+
+                let elapsed = now.duration_since(player.budget_refilled);
+                player.input_budget = (player.input_budget + elapsed).min(MAX_INPUT_BUDGET);
+                player.budget_refilled = now;
+
+                // inputs are sent until acknowledged, the ones already applied come back
+                let mut acknowledged = None;
+
+                for input in inputs.iter() {
+                    let applied = player
+                        .last_input
+                        .map_or(false, |last| input.sequence <= last);
+                    let duration = input.duration();
+
+                    if applied {
+                        continue;
+                    }
+
+                    // acknowledged without moving, the client takes the position back
+                    if duration > MAX_INPUT_DURATION {
+                        warn!("<server> refused an input from player {}", id);
+                    } else if duration > player.input_budget {
+                        // the client sends the rest again, once enough time has passed
+                        break;
+                    } else {
+                        let input = MovementInput {
+                            flying: input.flying && player.can_fly,
+                            ..*input
+                        };
+                        player.body = input.apply(player.body, &material);
+                        player.input_budget -= duration;
+                    }
+
+                    player.last_input = Some(input.sequence);
+                    acknowledged = Some(input.sequence);
+                }
+
+                player.state.position = player.body.position;
+                player.state.yaw = dequantize_angle(look.yaw);
+                player.state.pitch = dequantize_angle(look.pitch);
+                // the server knows better whether the player stands on something
+                player.state.flags = MovementFlags {
+                    flying: look.flags.flying && player.can_fly,
+                    on_ground: player.body.on_ground,
+                    ..look.flags
+                };
+
+                if let Some(sequence) = acknowledged {
+                    let body = player.body;
+                    self.send(
+                        id,
+                        ServerMessage::PositionAck {
+                            sequence,
+                            position: QuantizedPosition::new(body.position),
+                            velocity: body.velocity,
+                            on_ground: body.on_ground,
+                        },
+                    );
+                }
+
+                if let Some(time) = snapshot_ack {
+                    self.acknowledge_snapshot(id, time);
+                }
+            }
+            ClientMessage::SnapshotAck { time } => self.acknowledge_snapshot(id, time),
+            ClientMessage::EditBlock { position, block } => self.edit_block(id, position, block),
+            ClientMessage::Chat { text } => {
+                let text = text
+                    .chars()
+                    .filter(|c| !c.is_control())
+                    .take(MAX_MESSAGE_LENGTH)
+                    .collect::<String>();
+
+                if !text.trim().is_empty() {
+                    if let Some(player) = self.players.get(&id) {
+                        info!("<server> <{}> {}", player.username, text);
+                    }
+
+                    // the sender gets its message back, in the order the server saw it
+                    for recipient in self.joined_players() {
+                        let from = Some(id);
+                        let text = text.clone();
+                        self.send(recipient, ServerMessage::Chat { from, text });
+                    }
+                }
+            }
+        }
+    }
+
+    /// a session joins once, the client asks again when it resumes to get the server info
+    fn join(&mut self, id: PlayerId, username: Option<String>) {
+        let username = self.unique_username(id, username);
+
+        let newcomer = match self.players.get_mut(&id) {
+            Some(player) if !player.joined => {
+                player.joined = true;
+                player.username = username.clone();
+                true
+            }
+            Some(_) => false,
+            None => return,
+        };
+
+        let info = ServerEvent::ServerInfo {
+            seed: self.world.seed,
+            player_ids: self.joined_players(),
+        };
+        self.send(id, ServerMessage::Event(info));
+
+        let flying = self.players.get(&id).map_or(false, |player| player.can_fly);
+        self.send(id, ServerMessage::Abilities { flying });
+
+        if newcomer {
+            info!("<server> player {} joined as {}", id, username);
+
+            self.broadcast(
+                id,
+                ServerMessage::Event(ServerEvent::PlayerConnected { id }),
+            );
+            self.announce(id, format!("{} joined the game", username));
+            self.send_player_list();
+        }
+    }
+
+    /// the requested username, or a default one when it is invalid, with the id appended when
+    /// another player already goes by it
+    fn unique_username(&self, id: PlayerId, requested: Option<String>) -> String {
+        let username = requested
+            .filter(|username| is_valid_username(username))
+            .unwrap_or_else(|| default_username(id));
+
+        let taken = self
+            .players
+            .iter()
+            .any(|(other, player)| *other != id && player.joined && player.username == username);
+
+        if taken {
+            format!("{}_{}", username, id)
+        } else {
+            username
+        }
+    }
+
+    fn edit_block(&mut self, id: PlayerId, position: BlockCoordinate, block: u8) {
+        let in_reach = self.players.get(&id).map_or(false, |player| {
+            let eyes = player.state.position;
+            let center = position.center();
+            let distance = ((eyes.x - center.x).powi(2)
+                + (eyes.y - center.y).powi(2)
+                + (eyes.z - center.z).powi(2))
+            .sqrt();
+
+            player.joined && distance <= MAX_REACH
+        });
+
+        // air clears a block, anything else has to be a block the game knows
+        let known = block == AIR || self.block_registry.properties(block as _).is_some();
+
+        if !in_reach || !known || !set_block(&mut self.world, position, block) {
+            warn!(
+                "<server> refused edit of block {:?} by player {}",
+                position, id
+            );
+
+            // the client might already show its edit
+            if let Some(block) = block_at(&self.world, position) {
+                self.send(id, ServerMessage::BlockUpdate { position, block });
+            }
+
+            return;
+        }
+
+        // the others get the block with the chunk when they come closer
+        let chunk = position.chunk();
+        let recipients = self
+            .players
+            .iter()
+            .filter(|(_, player)| player.joined && player.sent_chunks.contains(&chunk))
+            .map(|(id, _)| *id)
+            .collect::<Vec<PlayerId>>();
+
+        for recipient in recipients {
+            self.send(recipient, ServerMessage::BlockUpdate { position, block });
+        }
+    }
+
+    /// drops the lost connections and forgets the players that did not come back
+    fn expire(&mut self, now: Instant) {
+        let players = &mut self.players;
+        let endpoints = &mut self.endpoints;
+
+        self.connections.retain(|peer, connection| {
+            let alive = !connection.timed_out(now);
+
+            if !alive {
+                warn!("<server> lost connection with {:?}", peer);
+                endpoints.close(*peer);
+
+                if let Some(player) = players.get_mut(&connection.session.player_id) {
+                    player.lost_at = Some(now);
+                }
+            }
+
+            alive
+        });
+
+        let expired = self
+            .players
+            .iter()
+            .filter(|(_, player)| {
+                player.lost_at.map_or(false, |lost_at| {
+                    now.duration_since(lost_at) >= SESSION_TIMEOUT
+                })
+            })
+            .map(|(id, _)| *id)
+            .collect::<Vec<PlayerId>>();
+
+        for id in expired {
+            info!("<server> session of player {} expired", id);
+            self.remove_player(id);
+        }
+    }
+
+    fn remove_player(&mut self, id: PlayerId) {
+        let endpoints = &mut self.endpoints;
+
+        self.connections.retain(|peer, connection| {
+            let kept = connection.session.player_id != id;
+
+            if !kept {
+                endpoints.close(*peer);
+            }
+
+            kept
+        });
+
+        if let Some(player) = self.players.remove(&id) {
+            if player.joined {
+                self.broadcast(
+                    id,
+                    ServerMessage::Event(ServerEvent::PlayerDisconnected { id }),
+                );
+                self.announce(id, format!("{} left the game", player.username));
+                self.send_player_list();
+            }
+        }
+    }
+
+    /// acks arrive out of order, only the latest one matters
+    fn acknowledge_snapshot(&mut self, id: PlayerId, time: ServerTime) {
+        if let Some(player) = self.players.get_mut(&id) {
+            if player.acked_snapshot.map_or(true, |acked| time > acked) {
+                player.acked_snapshot = Some(time);
+            }
+        }
+    }
+
+    /// Sends every player the others that changed since the last snapshot it acknowledged. Nothing
+    /// is sent when nothing changed and the client has the latest snapshot, which it keeps
+    /// displaying.
+    fn send_snapshots(&mut self) {
+        let time = self.time();
+
+        let states = self
+            .players
+            .iter()
+            .filter(|(_, player)| player.joined)
+            .map(|(id, player)| (*id, QuantizedState::new(&player.state)))
+            .collect::<Vec<(PlayerId, QuantizedState)>>();
+
+        for id in self.joined_players() {
+            let player = match self.players.get_mut(&id) {
+                Some(player) => player,
+                None => continue,
+            };
+
+            let others = states
+                .iter()
+                .filter(|(other, _)| *other != id)
+                .copied()
+                .collect::<HashMap<PlayerId, QuantizedState>>();
+
+            // the base might be too old to still be in the history, everything is sent again
+            let base = player
+                .acked_snapshot
+                .and_then(|acked| Some((acked, player.snapshots.get(acked)?)));
+            let players = EncodedSnapshot::encode(base, &others);
+
+            let up_to_date = player.acked_snapshot.is_some()
+                && player.acked_snapshot == player.snapshots.latest();
+
+            if (players.is_empty() && up_to_date) || (base.is_none() && others.is_empty()) {
+                continue;
+            }
+
+            player.snapshots.insert(time, others);
+            self.send(id, ServerMessage::Snapshot { time, players });
+        }
+    }
+
+    fn send_player_list(&mut self) {
+        let mut entries = self
+            .players
+            .iter()
+            .filter(|(_, player)| player.joined)
+            .map(|(id, player)| PlayerListEntry {
+                id: *id,
+                username: player.username.clone(),
+                ping: self.ping(*id),
+            })
+            .collect::<Vec<PlayerListEntry>>();
+        entries.sort_by_key(|entry| entry.id);
+
+        for id in self.joined_players() {
+            self.send(id, ServerMessage::PlayerList(entries.clone()));
+        }
+    }
+
+    /// round trip time in milliseconds, 0 until it was measured
+    fn ping(&self, id: PlayerId) -> u32 {
+        self.connections
+            .values()
+            .find(|connection| connection.session.player_id == id)
+            .and_then(|connection| connection.channel.rtt())
+            .map_or(0, |rtt| rtt.as_millis() as u32)
+    }
+
+    /// sends every player the chunks around it that it does not have yet
+    fn stream_chunks(&mut self) {
+        let positions = self
+            .players
+            .values()
+            .filter(|player| player.joined)
+            .map(|player| player.state.position)
+            .collect::<Vec<_>>();
+
+        if positions.is_empty() {
+            return;
+        }
+
+        self.world.load_around(positions);
+
+        for id in self.joined_players() {
+            let player = self.players.get_mut(&id).unwrap();
+            let center = chunk_coordinate(player.state.position);
+
+            player
+                .sent_chunks
+                .retain(|coords| !is_out_of_range(*coords, center));
+
+            let mut missing = chunks_in_range(center)
+                .into_iter()
+                .filter(|coords| !player.sent_chunks.contains(coords))
+                .collect::<Vec<ChunkGridCoordinate>>();
+            missing.sort_by_key(|coords| chunk_distance(*coords, center));
+
+            let mut chunks = Vec::new();
+
+            for coords in missing {
+                if chunks.len() >= CHUNKS_PER_UPDATE {
+                    break;
+                }
+
+                // a chunk group also needs the neighbours, generated a bit later on the edges
+                if let Some(group) = self.world.get_chunk_group(coords) {
+                    player.sent_chunks.insert(coords);
+                    chunks.push(ChunkData::from_group(&group));
+                }
+            }
+
+            for data in chunks {
+                self.send(id, ServerMessage::Chunk(data));
+            }
+        }
+    }
+
+    fn send(&mut self, id: PlayerId, message: ServerMessage) {
+        if let Some(payload) = encode(&message) {
+            self.send_payload(id, payload, delivery(&message));
+        }
+    }
+
+    /// sends the message to every player that joined, except `from`
+    fn broadcast(&mut self, from: PlayerId, message: ServerMessage) {
+        if let Some(payload) = encode(&message) {
+            for id in self.joined_players() {
+                if id != from {
+                    self.send_payload(id, payload.clone(), delivery(&message));
+                }
+            }
+        }
+    }
+
+    /// chat message from the server to everyone but `about`
+    fn announce(&mut self, about: PlayerId, text: String) {
+        self.broadcast(about, ServerMessage::Chat { from: None, text });
+    }
+
+    fn send_payload(&mut self, id: PlayerId, payload: Vec<u8>, delivery: Delivery) {
+        if let Some(connection) = self
+            .connections
+            .values_mut()
+            .find(|connection| connection.session.player_id == id)
+        {
+            connection.channel.send(payload, delivery);
+        }
+    }
+}
+
+fn default_username(id: PlayerId) -> String {
+    format!("Player{}", id)
+}
+
+/// what is sent continuously can be lost, the next one replaces it anyway
+fn delivery(message: &ServerMessage) -> Delivery {
+    match message {
+        ServerMessage::Snapshot { .. } => Delivery::Unreliable,
+        ServerMessage::PlayerList(_) => Delivery::Unreliable,
+        ServerMessage::Event(ServerEvent::PlayerMoved { .. }) => Delivery::Unreliable,
+        _ => Delivery::Reliable,
+    }
+}
+
+fn encode(message: &ServerMessage) -> Option<Vec<u8>> {
+    match bincode::serialize(message) {
+        Ok(payload) => Some(payload),
+        Err(err) => {
+            error!("<server> could not serialize message: {}", err);
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::{resume_proof, SessionCipher, TcpTransport, Transport};
+
+    /// the client end of a server, spoken to with raw packets
+    struct Client {
+        transport: Box<dyn Transport>,
+        exchange: KeyExchange,
+    }
+
+    impl Client {
+        fn new<T: Transport + 'static>(transport: T) -> Self {
+            Self {
+                transport: Box::new(transport),
+                exchange: KeyExchange::new(),
+            }
+        }
+
+        fn send(&mut self, packet: &Packet) {
+            self.transport
+                .send(&bincode::serialize(packet).unwrap())
+                .unwrap();
+        }
+
+        fn connect(&mut self, resume: Option<Resume>) {
+            let key = self.exchange.public_key();
+            self.send(&Packet::Connect {
+                protocol_version: PROTOCOL_VERSION,
+                resume,
+                key: Some(key),
+            });
+        }
+
+        fn receive(&mut self) -> Vec<Packet> {
+            let mut packets = Vec::new();
+
+            while let Some(data) = self.transport.receive().unwrap() {
+                packets.push(bincode::deserialize(&data).unwrap());
+            }
+
+            packets
+        }
+
+        fn accepted(&mut self) -> Option<(SessionCipher, Session)> {
+            let packets = self.receive();
+            self.open_accept(packets)
+        }
+
+        /// the cipher of the server key and the session of the sealed accept
+        fn open_accept(&mut self, packets: Vec<Packet>) -> Option<(SessionCipher, Session)> {
+            let key = packets.iter().find_map(|packet| match packet {
+                Packet::ServerKey { key } => Some(*key),
+                _ => None,
+            })?;
+            let mut cipher = self.exchange.complete(&key, Role::Client)?;
+
+            let session = packets.iter().find_map(|packet| match packet {
+                Packet::Sealed { counter, data } => match cipher.open(*counter, data)? {
+                    Packet::Accept { session, .. } => Some(session),
+                    _ => None,
+                },
+                _ => None,
+            })?;
+
+            Some((cipher, session))
+        }
+    }
+
+    fn time_responses(packets: Vec<Packet>, cipher: &mut SessionCipher) -> usize {
+        packets
+            .into_iter()
+            .filter_map(|packet| match packet {
+                Packet::Sealed { counter, data } => cipher.open(counter, &data),
+                _ => None,
+            })
+            .filter(|packet| matches!(packet, Packet::TimeResponse { .. }))
+            .count()
+    }
+
+    fn connected() -> (Server, Client, SessionCipher, Session) {
+        let (client_transport, server_transport) = MemoryTransport::pair();
+        let mut server = Server::new(server_transport);
+        let mut client = Client::new(client_transport);
+
+        client.connect(None);
+        server.update(Instant::now());
+        let (cipher, session) = client.accepted().expect("the handshake did not complete");
+
+        (server, client, cipher, session)
+    }
+
+    #[test]
+    fn completes_a_sealed_handshake() {
+        let (server, _, _, session) = connected();
+
+        let connection = &server.connections[&Peer::Local];
+        assert!(connection.is_encrypted());
+        assert_eq!(connection.session, session);
+    }
+
+    #[test]
+    fn completes_a_handshake_over_tcp() {
+        let (_local, server_transport) = MemoryTransport::pair();
+        let mut server = Server::new(server_transport);
+        let port = server.endpoints.open_lan(0).unwrap().port();
+
+        let transport = TcpTransport::connect(&format!("127.0.0.1:{}", port)).unwrap();
+        let mut client = Client::new(transport);
+        client.connect(None);
+
+        let deadline = Instant::now() + Duration::from_secs(2);
+        let mut packets = Vec::new();
+
+        // the key and the sealed accept
+        while packets.len() < 2 && Instant::now() < deadline {
+            server.update(Instant::now());
+            packets.extend(client.receive());
+            std::thread::sleep(Duration::from_millis(5));
+        }
+
+        let (_, session) = client
+            .open_accept(packets)
+            .expect("the handshake did not complete");
+        let (peer, connection) = server.connections.iter().next().unwrap();
+
+        assert!(matches!(peer, Peer::Tcp(_)));
+        assert_eq!(connection.session, session);
+    }
+
+    #[test]
+    fn drops_a_replayed_packet() {
+        let (mut server, mut client, mut cipher, _) = connected();
+        let request = cipher
+            .seal(&Packet::TimeRequest { client_time: 0 })
+            .unwrap();
+
+        client.send(&request);
+        server.update(Instant::now());
+        assert_eq!(time_responses(client.receive(), &mut cipher), 1);
+
+        client.send(&request);
+        server.update(Instant::now());
+        assert_eq!(time_responses(client.receive(), &mut cipher), 0);
+    }
+
+    #[test]
+    fn drops_a_tampered_packet() {
+        let (mut server, mut client, mut cipher, _) = connected();
+
+        let tampered = match cipher
+            .seal(&Packet::TimeRequest { client_time: 0 })
+            .unwrap()
+        {
+            Packet::Sealed { counter, mut data } => {
+                data[0] ^= 1;
+                Packet::Sealed { counter, data }
+            }
+            _ => unreachable!(),
+        };
+
+        client.send(&tampered);
+        server.update(Instant::now());
+        assert_eq!(time_responses(client.receive(), &mut cipher), 0);
+    }
+
+    #[test]
+    fn resumes_only_with_a_proof_of_the_token() {
+        let (mut server, mut client, _, session) = connected();
+
+        client.exchange = KeyExchange::new();
+        let key = client.exchange.public_key();
+        client.connect(Some(Resume::Proof {
+            player_id: session.player_id,
+            proof: resume_proof(session.token ^ 1, &key),
+        }));
+        server.update(Instant::now());
+        assert!(client.accepted().is_none());
+
+        client.connect(Some(Resume::Proof {
+            player_id: session.player_id,
+            proof: resume_proof(session.token, &key),
+        }));
+        server.update(Instant::now());
+        let (_, resumed) = client.accepted().expect("the session was not resumed");
+        assert_eq!(resumed, session);
+    }
+}
@@ -1,8 +1,10 @@
 pub mod c;
 mod identifiable;
 pub mod path;
+mod random;
 
 #[cfg(feature = "watchers")]
 pub mod watcher;
 
 pub use self::identifiable::Identifiable;
+pub use self::random::Random;